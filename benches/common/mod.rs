@@ -0,0 +1,85 @@
+//! Fixtures and timing shared by the benchmarks. They run the built binary
+//! on generated data folders, `BENCH_MESSAGES` sets how many messages each
+//! tab has.
+
+use std::env;
+use std::fs::{create_dir_all, remove_dir_all, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::time::{Duration, Instant};
+use chrono::NaiveDateTime;
+use fchat3_log_lib::FChatWriter;
+use fchat3_log_lib::fchat_message::{FChatMessage, FChatMessageType};
+
+const RUNS: u32 = 5;
+
+pub fn message_count() -> usize {
+    env::var("BENCH_MESSAGES").ok().and_then(|n| n.parse().ok()).unwrap_or(100_000)
+}
+
+/// A folder for the benchmark's data, removed when dropped.
+pub struct BenchDir(PathBuf);
+
+impl BenchDir {
+    pub fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("fchat3-log-merger-bench-{}-{}", name, process::id()));
+        let _ = remove_dir_all(&path);
+        create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+impl Drop for BenchDir {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.0);
+    }
+}
+
+/// Writes a data folder with a few tabs of `messages` messages, one every
+/// few seconds. `skip` leaves out every nth message and `shift` moves every
+/// message later by some seconds, so folders overlap without being copies.
+pub fn write_data_folder(folder: &Path, messages: usize, skip: usize, shift: i64) {
+    let logs = folder.join("alice").join("logs");
+    create_dir_all(&logs).unwrap();
+    for tab in ["bob", "carol", "#lounge"] {
+        let log_path = logs.join(tab);
+        let mut idx_buf = BufWriter::new(File::create(log_path.with_extension("idx")).unwrap());
+        let mut log_buf = BufWriter::new(File::create(&log_path).unwrap());
+        let mut w = FChatWriter::new(&mut idx_buf, tab.to_owned()).unwrap();
+        for i in (0..messages).filter(|i| skip == 0 || i % skip != 0) {
+            let message = FChatMessage {
+                datetime: NaiveDateTime::from_timestamp_opt(1_600_000_000 + i as i64 * 7 + shift, 0).unwrap(),
+                sender: if i % 2 == 0 { "Alice" } else { "Bob" }.to_owned(),
+                body: FChatMessageType::Message(format!("Message number {} in {}, with some more text after it.", i, tab)),
+            };
+            w.write_message(&mut log_buf, &mut idx_buf, message).unwrap();
+        }
+    }
+}
+
+/// Runs the merger with the args, timing the fastest of a few runs. The
+/// output folder is removed before each run.
+pub fn bench(name: &str, output: &Path, args: &[&str]) {
+    let mut fastest = Duration::MAX;
+    for _ in 0..RUNS {
+        let _ = remove_dir_all(output);
+        let start = Instant::now();
+        let status = Command::new(env!("CARGO_BIN_EXE_fchat3-log-merger"))
+            .args(args)
+            .arg("-o")
+            .arg(output)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        let elapsed = start.elapsed();
+        assert!(status.success(), "{} failed with {}", name, status);
+        fastest = fastest.min(elapsed);
+    }
+    println!("{:<48} {:>10.1?}", name, fastest);
+}
@@ -0,0 +1,22 @@
+//! Times merging overlapping folders with small and large time differences,
+//! the latter holding many more messages in the dedupe window.
+
+mod common;
+
+use common::{bench, message_count, write_data_folder, BenchDir};
+
+fn main() {
+    let messages = message_count();
+    let dir = BenchDir::new("dedupe");
+    let (a, b) = (dir.join("a"), dir.join("b"));
+    write_data_folder(&a, messages, 5, 0);
+    write_data_folder(&b, messages, 7, 1);
+    let output = dir.join("output");
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+
+    println!("Merging two folders of {} messages per tab.", messages);
+    bench("dedupe --time-diff 0s", &output, &["-f", a, b, "-d", "0s"]);
+    bench("dedupe --time-diff 5s", &output, &["-f", a, b, "-d", "5s"]);
+    bench("dedupe --time-diff 1h", &output, &["-f", a, b, "-d", "1h"]);
+    bench("dedupe --time-diff 1h --prefer longest-body", &output, &["-f", a, b, "-d", "1h", "--prefer", "longest-body"]);
+}
@@ -0,0 +1,20 @@
+//! Times reading logs through memory maps against buffered reads, with
+//! `--always-parse` so every message is read rather than copied.
+
+mod common;
+
+use common::{bench, message_count, write_data_folder, BenchDir};
+
+fn main() {
+    let messages = message_count();
+    let dir = BenchDir::new("mmap");
+    let (a, b) = (dir.join("a"), dir.join("b"));
+    write_data_folder(&a, messages, 5, 0);
+    write_data_folder(&b, messages, 7, 0);
+    let output = dir.join("output");
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+
+    println!("Merging two folders of {} messages per tab.", messages);
+    bench("buffered", &output, &["-f", a, b, "--always-parse"]);
+    bench("--mmap", &output, &["-f", a, b, "--always-parse", "--mmap"]);
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::Path;
+
+use crate::Error;
+
+/// Maps character folder or log names to the name they are merged under.
+///
+/// Unless case-sensitive, names that only differ by case are the same and
+/// aliases match regardless of case.
+#[derive(Debug, Default)]
+pub(crate) struct Aliases {
+    case_sensitive: bool,
+    aliases: HashMap<String, String>,
+}
+
+impl Aliases {
+    pub(crate) fn new(case_sensitive: bool) -> Self {
+        Self { case_sensitive, aliases: HashMap::new() }
+    }
+
+    /// Reads `old-name = new-name` lines from a file. Blank lines and lines
+    /// starting with `#` are ignored.
+    pub(crate) fn read_file(&mut self, path: &Path) -> Result<(), Error> {
+        let contents = read_to_string(path)
+            .map_err(|e| Error::UnableToOpenAliases(path.to_path_buf(), e))?;
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once('=') {
+                Some((old, new)) if !old.trim().is_empty() && !new.trim().is_empty() => {
+                    self.insert(old.trim(), new.trim());
+                },
+                _ => return Err(Error::BadAlias(path.to_path_buf(), line_number + 1)),
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn insert(&mut self, old: &str, new: &str) {
+        let key = self.key(old);
+        self.aliases.insert(key, new.to_owned());
+    }
+
+    /// How names are compared when deciding if they are the same.
+    pub(crate) fn key(&self, name: &str) -> String {
+        if self.case_sensitive {
+            name.to_owned()
+        } else {
+            name.to_lowercase()
+        }
+    }
+
+    /// Gets the name something is merged under.
+    pub(crate) fn resolve<'a>(&'a self, name: &'a str) -> &'a str {
+        match self.aliases.get(&self.key(name)) {
+            Some(new) => new,
+            None => name,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn reads_aliases_ignoring_comments_and_blank_lines() {
+        let dir = TempDir::new();
+        let path = dir.join("aliases");
+        write(&path, "# Renamed in 2021\n\n  Old Name =  New Name \n").unwrap();
+        let mut aliases = Aliases::new(false);
+        aliases.read_file(&path).unwrap();
+        assert_eq!(aliases.resolve("old name"), "New Name");
+        assert_eq!(aliases.resolve("Other"), "Other");
+
+        let mut aliases = Aliases::new(true);
+        aliases.read_file(&path).unwrap();
+        assert_eq!(aliases.resolve("Old Name"), "New Name");
+        assert_eq!(aliases.resolve("old name"), "old name");
+    }
+
+    #[test]
+    fn rejects_lines_that_are_not_aliases_with_their_line_number() {
+        let dir = TempDir::new();
+        for (contents, line) in [
+            ("A = B\nno equals sign\n", 2),
+            ("# comment\n\n = B\n", 3),
+            ("A =\n", 1),
+        ] {
+            let path = dir.join("aliases");
+            write(&path, contents).unwrap();
+            match Aliases::new(false).read_file(&path) {
+                Err(Error::BadAlias(p, n)) => assert_eq!((p, n), (path, line), "{:?}", contents),
+                other => panic!("{:?}: {:?}", contents, other),
+            }
+        }
+    }
+}
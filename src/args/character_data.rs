@@ -0,0 +1,17 @@
+use clap::ValueEnum;
+
+/// Whether to copy the files kept next to a character's logs, such as their
+/// settings, and which to keep when several sources have the same file.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum CharacterData {
+    /// The most recently modified copy.
+    #[default]
+    Newest,
+    /// The copy from the left-most source.
+    Left,
+    /// Every different copy, the left-most under the original name and the
+    /// others with `.source-N` appended.
+    KeepAll,
+    /// Don't copy them.
+    None,
+}
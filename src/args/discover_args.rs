@@ -0,0 +1,9 @@
+use std::path::PathBuf;
+use clap::Args;
+
+#[derive(Args, Debug)]
+pub(crate) struct DiscoverArgs {
+    /// Home folder to search in instead of the current user's.
+    #[clap(long)]
+    pub(crate) home: Option<PathBuf>,
+}
@@ -0,0 +1,27 @@
+use std::path::PathBuf;
+use clap::Args;
+
+use super::TimestampArg;
+
+#[derive(Args, Debug)]
+#[clap(group = clap::ArgGroup::new("range").required(true).multiple(true))]
+pub(crate) struct ExtractArgs {
+    /// Log to extract from, with its idx next to it, or a character's folder with --character.
+    pub(crate) input: PathBuf,
+
+    /// Log to write, or a folder to write every tab to with --character.
+    #[clap(short, long)]
+    pub(crate) output: PathBuf,
+
+    /// Only keep messages at or after this timestamp in YYYY-MM-DD HH:MM:SS.
+    #[clap(long, group = "range")]
+    pub(crate) from: Option<TimestampArg>,
+
+    /// Only keep messages before this timestamp in YYYY-MM-DD HH:MM:SS.
+    #[clap(long, group = "range")]
+    pub(crate) to: Option<TimestampArg>,
+
+    /// Extract from every tab of the character whose folder is given.
+    #[clap(long)]
+    pub(crate) character: bool,
+}
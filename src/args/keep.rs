@@ -0,0 +1,11 @@
+use clap::ValueEnum;
+
+/// Which messages to keep when merging sources.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum Keep {
+    /// Messages from any source.
+    #[default]
+    Union,
+    /// Only messages found in every source.
+    Intersection,
+}
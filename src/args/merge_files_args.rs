@@ -0,0 +1,13 @@
+use std::path::PathBuf;
+use clap::Args;
+
+#[derive(Args, Debug)]
+pub(crate) struct MergeFilesArgs {
+    /// Logs to merge, each with its idx next to it, left-most first.
+    #[clap(required = true, num_args = 1..)]
+    pub(crate) files: Vec<PathBuf>,
+
+    /// Log to write, with its idx written next to it.
+    #[clap(short, long)]
+    pub(crate) output: PathBuf,
+}
@@ -1,34 +1,174 @@
 use std::path::PathBuf;
 use chrono::Duration;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 mod time_diff_arg;
 use time_diff_arg::TimeDiffArg;
 mod timestamp_arg;
 use timestamp_arg::TimestampArg;
+mod pattern_arg;
+pub(crate) use pattern_arg::PatternArg;
+mod tab_name_rule;
+pub(crate) use tab_name_rule::TabNameRule;
+mod tab_title_arg;
+pub(crate) use tab_title_arg::TabTitleArg;
+mod prefer;
+pub(crate) use prefer::Prefer;
+mod keep;
+pub(crate) use keep::Keep;
+mod tab_conflict_arg;
+pub(crate) use tab_conflict_arg::TabConflictArg;
+mod out_of_order;
+pub(crate) use out_of_order::OutOfOrder;
+mod merge_files_args;
+pub(crate) use merge_files_args::MergeFilesArgs;
+mod split_by;
+pub(crate) use split_by::SplitBy;
+mod split_args;
+pub(crate) use split_args::SplitArgs;
+mod extract_args;
+pub(crate) use extract_args::ExtractArgs;
+mod pseudonym_arg;
+pub(crate) use pseudonym_arg::PseudonymArg;
+mod redact_args;
+pub(crate) use redact_args::RedactArgs;
+mod stats_format;
+pub(crate) use stats_format::StatsFormat;
+mod stats_args;
+pub(crate) use stats_args::StatsArgs;
+mod rename_character_args;
+pub(crate) use rename_character_args::RenameCharacterArgs;
+mod character_data;
+pub(crate) use character_data::CharacterData;
+mod discover_args;
+pub(crate) use discover_args::DiscoverArgs;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 #[command(
-    help_template = "{name} {version} \n{author-with-newline} {about-section} \n {usage-heading} {usage} \n {all-args} {tab}"
+    help_template = "{name} {version} \n{author-with-newline} {about-section} \n {usage-heading} {usage} \n {all-args} {tab}",
+    subcommand_negates_reqs = true
 )]
 pub(crate) struct Args {
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+
     /// What folders to read from.
-    #[clap(short, long, required = true, num_args = 2..)]
+    #[clap(short, long, required_unless_present = "auto", num_args = 1..)]
     pub(crate) folders: Vec<PathBuf>,
 
+    /// Also read from the F-Chat 3.0 data folders found in their default locations.
+    #[clap(long)]
+    pub(crate) auto: bool,
+
+    /// Home folder to search in when using --auto instead of the current user's.
+    #[clap(long, requires = "auto")]
+    pub(crate) home: Option<PathBuf>,
+
+    /// Search the folders for data folders nested inside them, such as in backups.
+    #[clap(short, long)]
+    pub(crate) recursive: bool,
+
+    /// How many folders deep to search with --recursive.
+    #[clap(long, value_name = "DEPTH", default_value_t = 6, requires = "recursive")]
+    pub(crate) max_depth: usize,
+
     /// How long the time difference between messages to check for duplicates specified in human time.
     #[clap(short = 'd', long, default_value_t = TimeDiffArg::from(Duration::zero()))]
     pub(crate) time_diff: TimeDiffArg,
 
+    /// Most messages to hold in memory while looking for duplicates. Duplicates further apart may be missed.
+    #[clap(long, value_name = "MESSAGES", default_value_t = 100_000, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub(crate) max_window: usize,
+
+    /// Which copy of a duplicated message to keep.
+    #[clap(long, value_enum, default_value_t = Prefer::Left)]
+    pub(crate) prefer: Prefer,
+
+    /// Whether to keep messages from any source or only those in every source.
+    #[clap(long, value_enum, default_value_t = Keep::Union)]
+    pub(crate) keep: Keep,
+
+    /// Override --prefer and --keep for a log, given as LOG=SETTING[,SETTING]. Can be repeated.
+    #[clap(long, value_name = "LOG=SETTINGS")]
+    pub(crate) tab_conflict: Vec<TabConflictArg>,
+
+    /// What to do with messages earlier than the one before them in the same source.
+    #[clap(long, value_enum, default_value_t = OutOfOrder::Warn)]
+    pub(crate) out_of_order: OutOfOrder,
+
+    /// How many messages ahead to look when sorting out of order messages.
+    #[clap(long, value_name = "MESSAGES", default_value_t = 1_000, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub(crate) reorder_window: usize,
+
     /// Assuming the left-most is up-to-date, skip to this timestamp in YYYY-MM-DD HH:MM:SS.
     #[clap(long)]
     pub(crate) fast_forward: Option<TimestampArg>,
 
+    /// Only keep messages at or after this timestamp in YYYY-MM-DD HH:MM:SS.
+    #[clap(long)]
+    pub(crate) since: Option<TimestampArg>,
+
+    /// Only keep messages before this timestamp in YYYY-MM-DD HH:MM:SS.
+    #[clap(long)]
+    pub(crate) until: Option<TimestampArg>,
+
+    /// Only merge characters matching this glob (or regex when prefixed with `re:`). Can be repeated.
+    #[clap(long = "character", value_name = "PATTERN")]
+    pub(crate) characters: Vec<PatternArg>,
+
+    /// Skip characters matching this glob (or regex when prefixed with `re:`). Can be repeated.
+    #[clap(long = "exclude-character", value_name = "PATTERN")]
+    pub(crate) exclude_characters: Vec<PatternArg>,
+
+    /// Only merge tabs whose log or tab name matches this glob (or regex when prefixed with `re:`). Can be repeated.
+    #[clap(long = "tab", value_name = "PATTERN")]
+    pub(crate) tabs: Vec<PatternArg>,
+
+    /// Skip tabs whose log or tab name matches this glob (or regex when prefixed with `re:`). Can be repeated.
+    #[clap(long = "exclude-tab", value_name = "PATTERN")]
+    pub(crate) exclude_tabs: Vec<PatternArg>,
+
+    /// Treat character folders and logs that only differ by case as different characters and tabs.
+    #[clap(long)]
+    pub(crate) case_sensitive: bool,
+
+    /// File of `old-name = new-name` lines to merge renamed characters under one name.
+    #[clap(long, value_name = "FILE")]
+    pub(crate) character_aliases: Option<PathBuf>,
+
+    /// File of `old-log-name = new-log-name` lines to merge renamed tabs under one log.
+    #[clap(long, value_name = "FILE")]
+    pub(crate) tab_aliases: Option<PathBuf>,
+
+    /// Which source to take a merged tab's name from.
+    #[clap(long, value_enum, default_value_t = TabNameRule::First)]
+    pub(crate) tab_name: TabNameRule,
+
+    /// Explicitly name a merged log's tab, given as LOG=TITLE. Can be repeated.
+    #[clap(long, value_name = "LOG=TITLE")]
+    pub(crate) tab_title: Vec<TabTitleArg>,
+
     /// Folder to write the merged logs to.
     #[clap(short, long, required_unless_present = "dry_run")]
     pub(crate) output: Option<PathBuf>,
 
+    /// Which copy of the files kept next to a character's logs, such as settings, to copy to the output.
+    #[clap(long, value_enum, default_value_t = CharacterData::Newest)]
+    pub(crate) character_data: CharacterData,
+
+    /// Re-read merged tabs to check they are valid, in order and have every message from their sources.
+    #[clap(long)]
+    pub(crate) verify: bool,
+
+    /// Keep what was merged when a merge fails instead of removing it.
+    #[clap(long)]
+    pub(crate) keep_partial: bool,
+
+    /// Continue an interrupted merge to the same output, skipping tabs it finished whose sources have not changed.
+    #[clap(long)]
+    pub(crate) resume: bool,
+
     /// Collects files, but does not do anything.
     #[clap(long)]
     pub(crate) dry_run: bool,
@@ -37,7 +177,47 @@ pub(crate) struct Args {
     #[clap(long)]
     pub(crate) dupe_warning: bool,
 
+    /// Compare messages even when sources are the same or older cuts of one another instead of copying.
+    #[clap(long)]
+    pub(crate) always_parse: bool,
+
+    /// Memory map logs when reading them instead of using buffered reads. Messages are still parsed into owned strings.
+    #[clap(long)]
+    pub(crate) mmap: bool,
+
+    /// How many threads to merge with. Defaults to one per CPU.
+    #[clap(short, long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub(crate) jobs: Option<usize>,
+
+    /// Most tabs to read and write at once, for slow or network drives.
+    #[clap(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub(crate) io_jobs: Option<usize>,
+
+    /// Most files to have open at once while merging.
+    #[clap(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(3..))]
+    pub(crate) max_open_files: Option<usize>,
+
     /// Increase verbosity. More occurances increases the verbosity.
-    #[clap(short, action = clap::ArgAction::Count)]
+    #[clap(short, action = clap::ArgAction::Count, global = true)]
     pub(crate) verbosity: u8
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum Command {
+    /// List F-Chat 3.0 data folders found in their default locations.
+    Discover(DiscoverArgs),
+    /// Merge log files given directly into one log, such as logs that are not in a data folder.
+    ///
+    /// Merge options such as --time-diff go before the subcommand.
+    MergeFiles(MergeFilesArgs),
+    /// Split a log into a log per day, month or year, or per so many messages, keeping its tab name.
+    Split(SplitArgs),
+    /// Copy the messages within a time range of a log, or of every tab of a character, into new logs.
+    Extract(ExtractArgs),
+    /// Rewrite logs with names pseudonymized, senders removed or text masked, for sharing them.
+    Redact(RedactArgs),
+    /// Print statistics about the logs in folders, per character and tab.
+    Stats(StatsArgs),
+    /// Move a renamed character's folder in a data folder, optionally rewriting their messages to the new name.
+    RenameCharacter(RenameCharacterArgs),
 }
\ No newline at end of file
@@ -0,0 +1,15 @@
+use clap::ValueEnum;
+
+/// What to do with messages earlier than the one before them in a source,
+/// such as after a clock change.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum OutOfOrder {
+    /// Keep the source's order and warn about it.
+    #[default]
+    Warn,
+    /// Sort messages within the reorder window, warning about any that are
+    /// still out of order.
+    Sort,
+    /// Keep the source's order without warning.
+    Preserve,
+}
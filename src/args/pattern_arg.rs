@@ -0,0 +1,44 @@
+use std::fmt::Display;
+use std::str::FromStr;
+use regex::{Regex, RegexBuilder};
+
+/// Tuple struct containing a name pattern, used for arg parsing.
+///
+/// Patterns starting with `re:` are regular expressions, anything else is a
+/// case-insensitive glob where `*` matches any run of characters and `?`
+/// matches a single one.
+#[derive(Debug, Clone)]
+pub(crate) struct PatternArg(Regex);
+
+impl PatternArg {
+    pub(crate) fn is_match(&self, name: &str) -> bool {
+        self.0.is_match(name)
+    }
+}
+
+impl FromStr for PatternArg {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(re) = s.strip_prefix("re:") {
+            return Ok(Self(Regex::new(re)?))
+        }
+        let mut re = String::with_capacity(s.len() + 2);
+        re.push('^');
+        for c in s.chars() {
+            match c {
+                '*' => re.push_str(".*"),
+                '?' => re.push('.'),
+                c => re.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        re.push('$');
+        Ok(Self(RegexBuilder::new(&re).case_insensitive(true).build()?))
+    }
+}
+
+impl Display for PatternArg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
@@ -0,0 +1,15 @@
+use clap::ValueEnum;
+
+/// Which copy of a duplicated message to keep.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum Prefer {
+    /// The copy from the left-most source.
+    #[default]
+    Left,
+    /// The copy from the most recently modified source.
+    NewestSource,
+    /// The copy with the longest body. Messages whose body is the start of
+    /// another's from the same sender are treated as duplicates, such as
+    /// ones cut short.
+    LongestBody,
+}
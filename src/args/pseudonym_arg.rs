@@ -0,0 +1,30 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Tuple struct containing a name and optionally the pseudonym to replace it
+/// with, used for arg parsing.
+#[derive(Debug, Clone)]
+pub(crate) struct PseudonymArg(pub(crate) String, pub(crate) Option<String>);
+
+impl FromStr for PseudonymArg {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((name, pseudonym)) if !name.is_empty() && !pseudonym.is_empty() => {
+                Ok(Self(name.to_owned(), Some(pseudonym.to_owned())))
+            },
+            None if !s.is_empty() => Ok(Self(s.to_owned(), None)),
+            _ => Err(crate::Error::BadPseudonym(s.to_owned())),
+        }
+    }
+}
+
+impl Display for PseudonymArg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.1 {
+            Some(pseudonym) => write!(f, "{}={}", self.0, pseudonym),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
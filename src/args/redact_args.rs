@@ -0,0 +1,32 @@
+use std::path::PathBuf;
+use clap::Args;
+use regex::Regex;
+
+use super::PseudonymArg;
+
+#[derive(Args, Debug)]
+pub(crate) struct RedactArgs {
+    /// Logs to redact, either with their idx next to them or exported as text.
+    #[clap(required = true, num_args = 1..)]
+    pub(crate) files: Vec<PathBuf>,
+
+    /// Folder to write the redacted logs to.
+    #[clap(short, long)]
+    pub(crate) output: PathBuf,
+
+    /// Replace a name everywhere with a pseudonym, `Person 1` and so on unless given as NAME=PSEUDONYM. Can be repeated.
+    #[clap(long, value_name = "NAME[=PSEUDONYM]")]
+    pub(crate) pseudonymize: Vec<PseudonymArg>,
+
+    /// Remove every message sent by this name. Can be repeated.
+    #[clap(long, value_name = "NAME")]
+    pub(crate) remove_sender: Vec<String>,
+
+    /// Mask matches of this regex in messages. Can be repeated.
+    #[clap(long, value_name = "REGEX")]
+    pub(crate) mask: Vec<Regex>,
+
+    /// What to replace masked text with.
+    #[clap(long, value_name = "TEXT", default_value = "[redacted]")]
+    pub(crate) mask_with: String,
+}
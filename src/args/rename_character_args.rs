@@ -0,0 +1,18 @@
+use std::path::PathBuf;
+use clap::Args;
+
+#[derive(Args, Debug)]
+pub(crate) struct RenameCharacterArgs {
+    /// Data folder holding the character's folder.
+    pub(crate) folder: PathBuf,
+
+    /// The character's old name.
+    pub(crate) old_name: String,
+
+    /// The character's new name.
+    pub(crate) new_name: String,
+
+    /// Also rewrite the sender of the character's own messages in every tab to the new name.
+    #[clap(long)]
+    pub(crate) rewrite_senders: bool,
+}
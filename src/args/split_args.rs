@@ -0,0 +1,23 @@
+use std::path::PathBuf;
+use clap::Args;
+
+use super::SplitBy;
+
+#[derive(Args, Debug)]
+#[clap(group = clap::ArgGroup::new("split").required(true))]
+pub(crate) struct SplitArgs {
+    /// Log to split, with its idx next to it.
+    pub(crate) log: PathBuf,
+
+    /// Folder to write the split logs to.
+    #[clap(short, long)]
+    pub(crate) output: PathBuf,
+
+    /// Split into a log per day, month or year.
+    #[clap(long, value_enum, group = "split")]
+    pub(crate) by: Option<SplitBy>,
+
+    /// Split into logs of at most this many messages.
+    #[clap(long, group = "split", value_parser = clap::value_parser!(u64).range(1..))]
+    pub(crate) messages: Option<u64>,
+}
@@ -0,0 +1,20 @@
+use clap::ValueEnum;
+
+/// What period of messages goes into each log when splitting.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SplitBy {
+    Day,
+    Month,
+    Year,
+}
+
+impl SplitBy {
+    /// Format of the part of a message's time naming its log.
+    pub(crate) fn format(self) -> &'static str {
+        match self {
+            Self::Day => "%Y-%m-%d",
+            Self::Month => "%Y-%m",
+            Self::Year => "%Y",
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use std::path::PathBuf;
+use clap::Args;
+
+use super::StatsFormat;
+
+#[derive(Args, Debug)]
+pub(crate) struct StatsArgs {
+    /// Folders to read from.
+    #[clap(required = true, num_args = 1..)]
+    pub(crate) folders: Vec<PathBuf>,
+
+    /// How to print the statistics.
+    #[clap(long, value_enum, default_value_t = StatsFormat::Table)]
+    pub(crate) format: StatsFormat,
+
+    /// How many of the most active senders to list.
+    #[clap(long, value_name = "SENDERS", default_value_t = 5)]
+    pub(crate) top: usize,
+}
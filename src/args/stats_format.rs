@@ -0,0 +1,11 @@
+use clap::ValueEnum;
+
+/// How statistics are printed.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum StatsFormat {
+    /// Tables for reading.
+    #[default]
+    Table,
+    /// JSON for other programs.
+    Json,
+}
@@ -0,0 +1,44 @@
+use std::fmt::Display;
+use std::str::FromStr;
+use clap::ValueEnum;
+
+use super::{Keep, Prefer};
+
+/// Tuple struct containing a log name and how to resolve its duplicates,
+/// parsed from `LOG=SETTING[,SETTING]`, used for arg parsing.
+#[derive(Debug, Clone)]
+pub(crate) struct TabConflictArg(pub(crate) String, pub(crate) Option<Prefer>, pub(crate) Option<Keep>);
+
+impl FromStr for TabConflictArg {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || crate::Error::BadTabConflict(s.to_owned());
+        let (log_name, settings) = match s.split_once('=') {
+            Some((log_name, settings)) if !log_name.is_empty() && !settings.is_empty() => (log_name, settings),
+            _ => return Err(bad()),
+        };
+        let mut arg = Self(log_name.to_owned(), None, None);
+        for setting in settings.split(',') {
+            if let Ok(prefer) = Prefer::from_str(setting, true) {
+                arg.1 = Some(prefer);
+            } else if let Ok(keep) = Keep::from_str(setting, true) {
+                arg.2 = Some(keep);
+            } else {
+                return Err(bad())
+            }
+        }
+        Ok(arg)
+    }
+}
+
+impl Display for TabConflictArg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let settings: Vec<String> = self.1.and_then(|p| p.to_possible_value())
+            .into_iter()
+            .chain(self.2.and_then(|k| k.to_possible_value()))
+            .map(|v| v.get_name().to_owned())
+            .collect();
+        write!(f, "{}={}", self.0, settings.join(","))
+    }
+}
@@ -0,0 +1,11 @@
+use clap::ValueEnum;
+
+/// Which source's idx to take the tab name from when merging.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum TabNameRule {
+    /// The left-most source with the tab.
+    #[default]
+    First,
+    /// The source whose idx was modified most recently.
+    Latest,
+}
@@ -0,0 +1,26 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Tuple struct containing a log name and the tab name to give it, used for
+/// arg parsing.
+#[derive(Debug, Clone)]
+pub(crate) struct TabTitleArg(pub(crate) String, pub(crate) String);
+
+impl FromStr for TabTitleArg {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((log_name, title)) if !log_name.is_empty() && !title.is_empty() => {
+                Ok(Self(log_name.to_owned(), title.to_owned()))
+            },
+            _ => Err(crate::Error::BadTabTitle(s.to_owned())),
+        }
+    }
+}
+
+impl Display for TabTitleArg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.0, self.1)
+    }
+}
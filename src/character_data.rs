@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+use std::fs::{copy, create_dir_all, read, read_dir};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use log::{debug, info};
+
+use crate::args::CharacterData;
+use crate::{Characters, Error, Logs};
+
+/// The character folders a character's logs came from with the position of
+/// the folder they are in, in that order.
+fn character_folders(folder_paths: &[PathBuf], logs: &Logs) -> Vec<(usize, PathBuf)> {
+    let mut folders: Vec<(usize, PathBuf)> = Vec::new();
+    for location in logs.values().flatten() {
+        // `<folder>/<character>/logs/<log>`
+        if let Some(character_folder) = location.parent().and_then(Path::parent) {
+            if !folders.iter().any(|(_, f)| f == character_folder) {
+                let position = character_folder.parent()
+                    .and_then(|p| folder_paths.iter().position(|root| root == p))
+                    .unwrap_or(folder_paths.len());
+                folders.push((position, character_folder.to_path_buf()));
+            }
+        }
+    }
+    folders.sort();
+    folders
+}
+
+/// Lists the files under a folder by their path relative to it, skipping
+/// `logs` at the top.
+fn list_files(root: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> Result<(), Error> {
+    let folder = root.join(relative);
+    for entry in read_dir(&folder).map_err(|e| Error::UnableToOpenDirectory(folder.clone(), e))? {
+        let entry = entry.map_err(|e| Error::UnableToOpenDirectory(folder.clone(), e))?;
+        let path = relative.join(entry.file_name());
+        if relative.as_os_str().is_empty() && entry.file_name() == "logs" {
+            continue
+        }
+        if root.join(&path).is_dir() {
+            list_files(root, &path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn modified(path: &Path) -> SystemTime {
+    path.metadata().and_then(|m| m.modified()).unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+fn copy_file(from: &Path, to: &Path) -> Result<(), Error> {
+    if let Some(parent) = to.parent() {
+        create_dir_all(parent).map_err(|e| Error::UnableToCreateDirectory(parent.to_path_buf(), e))?;
+    }
+    copy(from, to).map_err(|e| Error::UnableToCopy(from.to_path_buf(), e))?;
+    Ok(())
+}
+
+/// Copies one character's files from its source folders, given with the
+/// position of the folder they are in, returning how many were copied.
+fn copy_character(sources: &[(usize, PathBuf)], output: &Path, policy: CharacterData) -> Result<u64, Error> {
+    // Relative path to the sources that have it with their position, left-most first.
+    let mut files: BTreeMap<PathBuf, Vec<(usize, PathBuf)>> = BTreeMap::new();
+    for (position, source) in sources {
+        let mut found = Vec::new();
+        list_files(source, Path::new(""), &mut found)?;
+        for relative in found {
+            let path = source.join(&relative);
+            files.entry(relative).or_default().push((*position, path));
+        }
+    }
+
+    let mut copied = 0;
+    for (relative, copies) in files {
+        let to = output.join(&relative);
+        match policy {
+            CharacterData::Left => {
+                copy_file(&copies[0].1, &to)?;
+                copied += 1;
+            },
+            CharacterData::Newest => {
+                // Left-most wins between copies modified at the same time.
+                let newest = copies.iter().fold(&copies[0], |a, b| if modified(&b.1) > modified(&a.1) { b } else { a });
+                copy_file(&newest.1, &to)?;
+                copied += 1;
+            },
+            CharacterData::KeepAll => {
+                let mut kept: Vec<Vec<u8>> = Vec::new();
+                for (n, (position, from)) in copies.iter().enumerate() {
+                    let contents = read(from).map_err(|e| Error::UnableToCopy(from.clone(), e))?;
+                    if kept.contains(&contents) {
+                        continue
+                    }
+                    let to = if n == 0 {
+                        to.clone()
+                    } else {
+                        let mut name = to.file_name().unwrap().to_os_string();
+                        name.push(format!(".source-{}", position + 1));
+                        to.with_file_name(name)
+                    };
+                    copy_file(from, &to)?;
+                    kept.push(contents);
+                    copied += 1;
+                }
+            },
+            CharacterData::None => {},
+        }
+    }
+    Ok(copied)
+}
+
+/// Copies the files kept next to each character's logs, such as settings,
+/// into the output so it can be used by the client as-is.
+pub(crate) fn copy_character_data(
+    characters: &Characters,
+    folder_paths: &[PathBuf],
+    output_path: &Path,
+    policy: CharacterData
+) -> Result<(), Error> {
+    if policy == CharacterData::None {
+        return Ok(())
+    }
+    let mut total = 0;
+    for (character_name, logs) in characters {
+        let sources = character_folders(folder_paths, logs);
+        let copied = copy_character(&sources, &output_path.join(character_name), policy)?;
+        debug!("Copied {} files for {}.", copied, character_name);
+        total += copied;
+    }
+    info!("Copied {} character data files.", total);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{read_to_string, write};
+    use super::*;
+    use crate::test_util::{message, write_tab, TempDir};
+
+    #[test]
+    fn names_kept_copies_after_the_position_of_their_folder() {
+        let dir = TempDir::new();
+        let folder_paths = vec![dir.join("a"), dir.join("b"), dir.join("c")];
+        let mut logs = Logs::new();
+        write_tab(&folder_paths[0], "Bob", "carol", &[message(0, "Bob", "Hi")]);
+        for (i, folder) in folder_paths.iter().enumerate().skip(1) {
+            let log = write_tab(folder, "Alice", "carol", &[message(0, "Alice", "Hi")]);
+            logs.entry("carol".to_owned()).or_default().push(log);
+            write(folder.join("Alice").join("settings"), format!("from {}", i)).unwrap();
+        }
+        let mut characters = Characters::new();
+        characters.insert("Alice".to_owned(), logs);
+
+        let output = dir.join("output");
+        copy_character_data(&characters, &folder_paths, &output, CharacterData::KeepAll).unwrap();
+        assert_eq!(read_to_string(output.join("Alice").join("settings")).unwrap(), "from 1");
+        assert_eq!(read_to_string(output.join("Alice").join("settings.source-3")).unwrap(), "from 2");
+        assert!(!output.join("Alice").join("settings.source-2").exists());
+    }
+}
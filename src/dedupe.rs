@@ -0,0 +1,466 @@
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::BufWriter;
+use std::iter::Peekable;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use chrono::NaiveDateTime;
+use fchat3_log_lib::FChatWriter;
+use fchat3_log_lib::fchat_message::{FChatMessage, FChatMessageType};
+use log::{debug, trace, warn};
+
+use crate::{Error, Keep, MergeOptions, Prefer, Reader, SortedMessage};
+
+pub(crate) fn format_message(message: &FChatMessage) -> String {
+    use fchat3_log_lib::fchat_message::FChatMessageType::*;
+    format!("[{}] {}", message.datetime,
+    match &message.body {
+        Message(m)  => format!("{}: {}",   message.sender, m),
+        Action(m)   => format!("{}{}",     message.sender, m),
+        Ad(m)       => format!("{}^ {}",   message.sender, m),
+        Roll(m)     => format!("* {}{}",   message.sender, m),
+        Warn(m)     => format!("! {}: {}", message.sender, m),
+        Event(m)    => format!("? {}{}",   message.sender, m),
+    })
+
+}
+
+/// Splits a body into something hashable.
+pub(crate) fn body_parts(body: &FChatMessageType) -> (u8, &str) {
+    use fchat3_log_lib::fchat_message::FChatMessageType::*;
+    match body {
+        Message(m) => (0, m),
+        Action(m)  => (1, m),
+        Ad(m)      => (2, m),
+        Roll(m)    => (3, m),
+        Warn(m)    => (4, m),
+        Event(m)   => (5, m),
+    }
+}
+
+pub(crate) fn content_hash(message: &FChatMessage) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    message.sender.hash(&mut hasher);
+    body_parts(&message.body).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Hash of the sender and kind of message, leaving out the body.
+fn sender_hash(message: &FChatMessage) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    message.sender.hash(&mut hasher);
+    body_parts(&message.body).0.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// How duplicates are resolved for a tab.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Conflict {
+    pub(crate) prefer: Prefer,
+    pub(crate) keep: Keep,
+}
+
+/// Decides which copy of a duplicated message is kept.
+pub(crate) struct Resolver {
+    conflict: Conflict,
+    /// Rank of each source, lower is preferred.
+    ranks: Vec<usize>,
+}
+
+impl Resolver {
+    /// Ranks the sources of a tab, reading their modification times when
+    /// the newest source is preferred.
+    pub(crate) fn new(conflict: Conflict, locations: &[PathBuf]) -> Result<Self, Error> {
+        let mut order: Vec<usize> = (0..locations.len()).collect();
+        if conflict.prefer == Prefer::NewestSource {
+            let mut modified = Vec::with_capacity(locations.len());
+            for p in locations {
+                let metadata = p.metadata().map_err(|e| Error::UnableToOpenLog(p.clone(), e))?;
+                modified.push(metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH));
+            }
+            // Stable, so the left-most wins between sources modified at the same time.
+            order.sort_by(|a, b| modified[*b].cmp(&modified[*a]));
+        }
+        let mut ranks = vec![0; locations.len()];
+        for (rank, source) in order.into_iter().enumerate() {
+            ranks[source] = rank;
+        }
+        Ok(Self { conflict, ranks })
+    }
+
+    /// Messages whose body starts another's are duplicates when keeping the longest.
+    fn loose(&self) -> bool {
+        self.conflict.prefer == Prefer::LongestBody
+    }
+
+    pub(crate) fn key(&self, message: &FChatMessage) -> u64 {
+        if self.loose() { sender_hash(message) } else { content_hash(message) }
+    }
+
+    pub(crate) fn is_duplicate(&self, a: &FChatMessage, b: &FChatMessage) -> bool {
+        if a.sender != b.sender {
+            return false
+        }
+        if !self.loose() {
+            return a.body == b.body
+        }
+        let (a_kind, a_body) = body_parts(&a.body);
+        let (b_kind, b_body) = body_parts(&b.body);
+        a_kind == b_kind && (a_body.starts_with(b_body) || b_body.starts_with(a_body))
+    }
+
+    /// If the new copy of a message should replace the kept one.
+    fn prefers(&self, new: &FChatMessage, new_source: usize, kept: &FChatMessage, kept_source: usize) -> bool {
+        if self.loose() {
+            let new_len = body_parts(&new.body).1.len();
+            let kept_len = body_parts(&kept.body).1.len();
+            if new_len != kept_len {
+                return new_len > kept_len
+            }
+        }
+        self.ranks[new_source] < self.ranks[kept_source]
+    }
+
+    /// If a kept message is written out rather than dropped.
+    fn keeps(&self, entry: &WindowEntry) -> bool {
+        self.conflict.keep == Keep::Union || entry.sources.len() == self.ranks.len()
+    }
+}
+
+/// A kept message that later messages are compared against.
+struct WindowEntry {
+    message: FChatMessage,
+    /// When the message is placed, see `deduplicate_messages`.
+    placed: NaiveDateTime,
+    /// Source the kept copy came from.
+    source: usize,
+    /// Sources that had this message, each once.
+    sources: Vec<usize>,
+}
+
+/// Kept messages that have not been written yet, oldest first, indexed by
+/// sender and body.
+struct Window<'a> {
+    resolver: &'a Resolver,
+    /// Entries moved to the back leave a gap, which is never at the front.
+    entries: VecDeque<Option<WindowEntry>>,
+    /// How many gaps there are.
+    gaps: usize,
+    /// Sequence number of the front entry.
+    front: u64,
+    /// Content hash to sequence numbers of the entries with it.
+    index: HashMap<u64, VecDeque<u64>>,
+    /// Largest the window has been.
+    peak: usize,
+}
+
+impl<'a> Window<'a> {
+    fn new(resolver: &'a Resolver) -> Self {
+        Self { resolver, entries: VecDeque::new(), gaps: 0, front: 0, index: HashMap::new(), peak: 0 }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len() - self.gaps
+    }
+
+    fn front_placed(&self) -> Option<NaiveDateTime> {
+        self.entries.front().and_then(Option::as_ref).map(|e| e.placed)
+    }
+
+    fn entry(&self, seq: u64) -> &WindowEntry {
+        // The index never points at gaps.
+        self.entries[(seq - self.front) as usize].as_ref().unwrap()
+    }
+
+    fn push_entry(&mut self, entry: WindowEntry) {
+        let seq = self.front + self.entries.len() as u64;
+        self.index.entry(self.resolver.key(&entry.message)).or_default().push_back(seq);
+        self.entries.push_back(Some(entry));
+        self.peak = self.peak.max(self.len());
+    }
+
+    fn push(&mut self, message: FChatMessage, placed: NaiveDateTime, source: usize) {
+        self.push_entry(WindowEntry { message, placed, source, sources: vec![source] });
+    }
+
+    fn pop_front(&mut self) -> Option<WindowEntry> {
+        let entry = self.entries.pop_front()?.unwrap();
+        let hash = self.resolver.key(&entry.message);
+        if let Some(seqs) = self.index.get_mut(&hash) {
+            // Entries are pushed in order, so this one is the oldest with the hash.
+            seqs.pop_front();
+            if seqs.is_empty() {
+                self.index.remove(&hash);
+            }
+        }
+        self.front += 1;
+        self.skip_gaps();
+        Some(entry)
+    }
+
+    fn skip_gaps(&mut self) {
+        while let Some(None) = self.entries.front() {
+            self.entries.pop_front();
+            self.gaps -= 1;
+            self.front += 1;
+        }
+    }
+
+    /// Finds the oldest entry the message is a duplicate of, and how many
+    /// entries it could have been a duplicate of. Like copies from other
+    /// sources, a message repeated within one source is a duplicate.
+    fn find(&self, message: &FChatMessage) -> (Option<u64>, usize) {
+        let mut found = None;
+        let mut hits = 0;
+        if let Some(seqs) = self.index.get(&self.resolver.key(message)) {
+            for seq in seqs {
+                let entry = self.entry(*seq);
+                if self.resolver.is_duplicate(&entry.message, message) {
+                    trace!("Duplicate Hit:\n{}\n{}", format_message(message), format_message(&entry.message));
+                    found.get_or_insert(*seq);
+                    hits += 1;
+                }
+            }
+        }
+        (found, hits)
+    }
+
+    /// Marks the entry as found in the source, keeping the source's copy
+    /// instead if it is preferred. A copy from another time is placed with
+    /// the message it came with, moving the entry to the back so it is still
+    /// written in order.
+    fn claim(&mut self, seq: u64, message: FChatMessage, placed: NaiveDateTime, source: usize) {
+        let at = (seq - self.front) as usize;
+        let entry = self.entries[at].as_mut().unwrap();
+        if !entry.sources.contains(&source) {
+            entry.sources.push(source);
+        }
+        if !self.resolver.prefers(&message, source, &entry.message, entry.source) {
+            return
+        }
+        if message.datetime == entry.message.datetime {
+            entry.message = message;
+            entry.source = source;
+            return
+        }
+
+        let mut entry = self.entries[at].take().unwrap();
+        self.gaps += 1;
+        // Copies share their key, so the entry stays under it.
+        let hash = self.resolver.key(&entry.message);
+        if let Some(seqs) = self.index.get_mut(&hash) {
+            seqs.retain(|s| *s != seq);
+        }
+        entry.message = message;
+        entry.placed = placed;
+        entry.source = source;
+        self.push_entry(entry);
+        self.skip_gaps();
+    }
+}
+
+/// Merges the readers by time, dropping messages with the same sender and
+/// body as a kept message from another source within the time difference.
+/// Which copy is kept, and if messages missing from some sources are, is up
+/// to the resolver.
+///
+/// A message earlier than one before it in the same source is placed at the
+/// latest time seen from that source, so each source's order is kept.
+///
+/// Only the heads of each reader and the kept messages within the time
+/// difference are held in memory, the latter being capped by the max window.
+/// Returns how many messages were written.
+pub(crate) fn deduplicate_messages(
+    mut readers: Vec<Peekable<Reader>>,
+    tab_name: String,
+    merge_options: &MergeOptions,
+    resolver: &Resolver,
+    mut w: FChatWriter,
+    log_buf: &mut BufWriter<File>,
+    idx_buf: &mut BufWriter<File>,
+) -> Result<u64, Error> {
+    let mut heads = BinaryHeap::with_capacity(readers.len());
+    // Latest time seen from and messages read from each reader.
+    let mut latest = vec![NaiveDateTime::MIN; readers.len()];
+    let mut read = vec![0; readers.len()];
+    for (index, reader) in readers.iter_mut().enumerate() {
+        if let Some(message) = reader.next() {
+            let message = message?;
+            latest[index] = message.datetime;
+            heads.push(Reverse(SortedMessage::new(message, index, 0)));
+        }
+    }
+
+    let mut window = Window::new(resolver);
+    let mut window_full = false;
+    let mut written = 0;
+    while let Some(Reverse(SortedMessage { message, placed, source: index, .. })) = heads.pop() {
+        if let Some(next_message) = readers[index].next() {
+            let mut next = SortedMessage::new(next_message?, index, read[index] + 1);
+            latest[index] = latest[index].max(next.placed);
+            next.placed = latest[index];
+            read[index] += 1;
+            heads.push(Reverse(next));
+        }
+
+        /* Anything older than the time-diff can no longer have duplicates
+            and gets written out. Even with a time-diff of 0, duplicate
+            messages made at the same time are discarded to account for
+            "syncing" an old log with an updated one.
+        */
+        while let Some(front_placed) = window.front_placed() {
+            let expired = front_placed + merge_options.time_diff < placed;
+            if !expired && window.len() < merge_options.max_window {
+                break
+            }
+            if !expired && !window_full {
+                warn!("{} hit the window limit of {} messages, some duplicates may be missed.",
+                    tab_name,
+                    merge_options.max_window
+                );
+                window_full = true;
+            }
+            let entry = window.pop_front().unwrap();
+            if resolver.keeps(&entry) {
+                debug!("Committing message:\n[{}] {}", tab_name, format_message(&entry.message));
+                w.write_message(log_buf, idx_buf, entry.message)?;
+                written += 1;
+            } else {
+                trace!("Dropping message missing from some sources:\n[{}] {}", tab_name, format_message(&entry.message));
+            }
+        }
+
+        match window.find(&message) {
+            (Some(seq), hits) => {
+                if merge_options.dupe_warning {
+                    warn!("Message was duplicated {} times:\n{}", hits, format_message(&message));
+                }
+                window.claim(seq, message, placed, index);
+            },
+            (None, _) => window.push(message, placed, index),
+        }
+    }
+
+    while let Some(entry) = window.pop_front() {
+        if resolver.keeps(&entry) {
+            debug!("Committing message:\n[{}] {}", tab_name, format_message(&entry.message));
+            w.write_message(log_buf, idx_buf, entry.message)?;
+            written += 1;
+        } else {
+            trace!("Dropping message missing from some sources:\n[{}] {}", tab_name, format_message(&entry.message));
+        }
+    }
+    debug!("Largest window for {} was {} messages.", tab_name, window.peak);
+    trace!("finished {}", tab_name);
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, read, remove_dir_all};
+    use std::path::Path;
+    use std::thread::sleep;
+    use std::time::{Duration, SystemTime};
+    use crate::test_util::{merge_into, merge_options, merge_sources, message, read_log, write_tab, TempDir};
+    use crate::{merge_tab, verify_tab};
+
+    fn modified(path: &Path) -> SystemTime {
+        path.metadata().unwrap().modified().unwrap()
+    }
+
+    #[test]
+    fn drops_copies_from_other_sources() {
+        let a = [message(0, "Alice", "Hi"), message(10, "Bob", "Hey")];
+        let b = [message(1, "Alice", "Hi"), message(20, "Bob", "Bye")];
+        let merged = merge_sources(&[&a, &b], &merge_options(&["-d", "5s"]));
+        assert_eq!(merged, vec![a[0].clone(), a[1].clone(), b[1].clone()]);
+    }
+
+    #[test]
+    fn drops_repeats_within_a_source() {
+        let a = [message(0, "Alice", "Hi"), message(2, "Alice", "Hi"), message(60, "Alice", "Hi")];
+        let b = [message(30, "Bob", "Hey")];
+        let merged = merge_sources(&[&a, &b], &merge_options(&["-d", "5s"]));
+        assert_eq!(merged, vec![a[0].clone(), b[0].clone(), a[2].clone()]);
+    }
+
+    #[test]
+    fn keeps_messages_outside_the_time_diff() {
+        let a = [message(0, "Alice", "Hi")];
+        let b = [message(10, "Alice", "Hi")];
+        let merged = merge_sources(&[&a, &b], &merge_options(&["-d", "5s"]));
+        assert_eq!(merged, vec![a[0].clone(), b[0].clone()]);
+    }
+
+    #[test]
+    fn merges_messages_sharing_a_timestamp_the_same_way_every_time() {
+        let a = [message(0, "Alice", "Hi"), message(0, "Alice", "How are you?"), message(5, "Bob", "Hey")];
+        let b = [message(0, "Bob", "Morning"), message(0, "Alice", "Hi"), message(5, "Carol", "Hello")];
+        let c = [message(0, "Carol", "Yo"), message(5, "Carol", "Hello"), message(5, "Alice", "Hello")];
+        let options = merge_options(&["--always-parse"]);
+        let merged = merge_sources(&[&a, &b, &c], &options);
+        assert_eq!(merged, vec![
+            a[0].clone(), a[1].clone(), b[0].clone(), c[0].clone(),
+            a[2].clone(), b[2].clone(), c[2].clone(),
+        ]);
+
+        let (first, second) = (TempDir::new(), TempDir::new());
+        let (_, first) = merge_into(&first, &[&a, &b, &c], &options);
+        let (_, second) = merge_into(&second, &[&a, &b, &c], &options);
+        assert_eq!(read(&first.log_path).unwrap(), read(&second.log_path).unwrap());
+        assert_eq!(read(first.log_path.with_extension("idx")).unwrap(), read(second.log_path.with_extension("idx")).unwrap());
+    }
+
+    #[test]
+    fn resolves_conflicts_as_asked() {
+        let a = [message(0, "Alice", "Hi"), message(2, "Bob", "Hey"), message(20, "Carol", "Only in a")];
+        let b = [message(4, "Alice", "Hi there"), message(5, "Bob", "Hey"), message(21, "Dave", "Only in b")];
+        // Kept copies are written at their own time, after messages that came before them.
+        let exact_union = vec![a[0].clone(), a[1].clone(), b[0].clone(), a[2].clone(), b[2].clone()];
+        for (prefer, keep, expected) in [
+            ("left", "union", exact_union),
+            ("longest-body", "union", vec![a[1].clone(), b[0].clone(), a[2].clone(), b[2].clone()]),
+            ("left", "intersection", vec![a[1].clone()]),
+            ("longest-body", "intersection", vec![a[1].clone(), b[0].clone()]),
+        ] {
+            let dir = TempDir::new();
+            let options = merge_options(&["-d", "5s", "--prefer", prefer, "--keep", keep]);
+            let (locations, written) = merge_into(&dir, &[&a, &b], &options);
+            assert_eq!(read_log(&written.log_path), expected, "--prefer {} --keep {}", prefer, keep);
+            verify_tab(&written, &locations, options.conflict, &options)
+                .unwrap_or_else(|e| panic!("--prefer {} --keep {}: {}", prefer, keep, e));
+        }
+    }
+
+    #[test]
+    fn keeps_the_time_of_the_preferred_copy() {
+        let a = [message(0, "Alice", "Hi"), message(10, "Carol", "Bye")];
+        let b = [message(2, "Bob", "Hey"), message(4, "Alice", "Hi")];
+        for (prefer, keep, expected) in [
+            ("left", "union", vec![a[0].clone(), b[0].clone(), a[1].clone()]),
+            ("newest-source", "union", vec![b[0].clone(), b[1].clone(), a[1].clone()]),
+            ("left", "intersection", vec![a[0].clone()]),
+            ("newest-source", "intersection", vec![b[1].clone()]),
+        ] {
+            let dir = TempDir::new();
+            let locations = [write_tab(&dir.join("source0"), "alice", "bob", &a), dir.join("source1/alice/logs/bob")];
+            // The right source is written last, so it is the newest.
+            write_tab(&dir.join("source1"), "alice", "bob", &b);
+            while modified(&locations[1]) <= modified(&locations[0]) {
+                sleep(Duration::from_millis(10));
+                remove_dir_all(dir.join("source1")).unwrap();
+                write_tab(&dir.join("source1"), "alice", "bob", &b);
+            }
+            let output = dir.join("output");
+            create_dir_all(&output).unwrap();
+            let options = merge_options(&["-d", "5s", "--prefer", prefer, "--keep", keep]);
+            let written = merge_tab("alice", "bob", &locations, &output, &options).unwrap();
+            assert_eq!(read_log(&written.log_path), expected, "--prefer {} --keep {}", prefer, keep);
+            verify_tab(&written, &locations, options.conflict, &options)
+                .unwrap_or_else(|e| panic!("--prefer {} --keep {}: {}", prefer, keep, e));
+        }
+    }
+}
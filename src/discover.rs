@@ -0,0 +1,174 @@
+use std::env;
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+use log::{info, trace};
+use humansize::{FormatSize, DECIMAL};
+
+use crate::{collect_logs, Aliases, Error, Filters};
+
+/// Where the F-Chat 3.0 client keeps its data folder relative to the home
+/// folder. All of them are checked regardless of the current OS so copied
+/// home folders (or test fixtures) are found too.
+const DATA_FOLDERS: &[&[&str]] = &[
+    // Linux
+    &[".config", "fchat", "data"],
+    // macOS
+    &["Library", "Application Support", "fchat", "data"],
+    // Windows
+    &["AppData", "Roaming", "fchat", "data"],
+];
+
+/// Gets the current user's home folder.
+pub(crate) fn home_folder() -> Result<PathBuf, Error> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .filter(|h| !h.is_empty())
+        .map(PathBuf::from)
+        .ok_or(Error::NoHomeFolder)
+}
+
+/// Finds the data folders that exist under the given home folder.
+pub(crate) fn find_data_folders(home: &Path) -> Vec<PathBuf> {
+    DATA_FOLDERS.iter()
+        .map(|parts| parts.iter().fold(home.to_path_buf(), |p, part| p.join(part)))
+        .filter(|p| {
+            trace!("Checking {:?}", p);
+            p.is_dir()
+        })
+        .collect()
+}
+
+/// Checks if a folder holds character folders, that is if any of its folders
+/// has a `logs` folder with an idx in it.
+pub(crate) fn is_data_folder(folder: &Path) -> bool {
+    let entries = match read_dir(folder) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+    entries.flatten()
+        .map(|e| e.path().join("logs"))
+        .filter(|logs| logs.is_dir())
+        .any(|logs| match read_dir(&logs) {
+            Ok(files) => files.flatten().any(|f| f.path().extension() == Some("idx".as_ref())),
+            Err(_) => false,
+        })
+}
+
+/// Finds data folders at or under a folder, up to `max_depth` folders deep.
+/// Data folders are not searched further and symbolic links are not
+/// followed.
+pub(crate) fn find_nested_data_folders(root: &Path, max_depth: usize) -> Result<Vec<PathBuf>, Error> {
+    let mut found = Vec::new();
+    let mut pending = vec![(root.to_path_buf(), 0)];
+    while let Some((folder, depth)) = pending.pop() {
+        trace!("Searching {:?}", folder);
+        if is_data_folder(&folder) {
+            found.push(folder);
+            continue
+        }
+        if depth == max_depth {
+            continue
+        }
+        for entry in read_dir(&folder).map_err(|e| Error::UnableToOpenDirectory(folder.clone(), e))? {
+            let entry = entry.map_err(|e| Error::UnableToOpenDirectory(folder.clone(), e))?;
+            if matches!(entry.file_type(), Ok(t) if t.is_dir()) {
+                pending.push((entry.path(), depth + 1));
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// Lists the data folders found under the home folder with what is in them.
+pub(crate) fn list_data_folders(home: Option<PathBuf>) -> Result<(), Error> {
+    let home = match home {
+        Some(h) => h,
+        None => home_folder()?,
+    };
+    let folders = find_data_folders(&home);
+    if folders.is_empty() {
+        info!("No F-Chat 3.0 data folders found in {}.", home.to_string_lossy());
+        return Ok(());
+    }
+    for folder in folders {
+        let (characters, size_total, file_total) = collect_logs(
+            vec![folder.clone()],
+            &Filters::default(),
+            &Aliases::default(),
+            &Aliases::default()
+        )?;
+        info!("{}: {} characters, {} files, {}.",
+            folder.to_string_lossy(),
+            characters.len(),
+            file_total,
+            size_total.format_size(DECIMAL)
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{message, write_tab, TempDir};
+
+    #[test]
+    fn finds_data_folders_under_a_custom_home() {
+        let home = TempDir::new();
+        let linux = home.join(".config/fchat/data");
+        write_tab(&linux, "Character", "tab", &[message(0, "Character", "hi")]);
+        // Only folders that exist are found.
+        assert_eq!(find_data_folders(home.path()), vec![linux.clone()]);
+
+        let windows = home.join("AppData/Roaming/fchat/data");
+        write_tab(&windows, "Other", "tab", &[message(0, "Other", "hi")]);
+        assert_eq!(find_data_folders(home.path()), vec![linux, windows]);
+        assert!(list_data_folders(Some(home.path().to_path_buf())).is_ok());
+    }
+
+    #[test]
+    fn finds_nothing_in_an_empty_home() {
+        let home = TempDir::new();
+        assert!(find_data_folders(home.path()).is_empty());
+        assert!(list_data_folders(Some(home.path().to_path_buf())).is_ok());
+    }
+
+    /// Writes a data folder with one character and tab.
+    fn data_folder(path: &Path) -> PathBuf {
+        write_tab(path, "Character", "tab", &[message(0, "Character", "hi")]);
+        path.to_path_buf()
+    }
+
+    #[test]
+    fn finds_nested_data_folders_up_to_the_max_depth() {
+        let root = TempDir::new();
+        let one = data_folder(&root.join("one"));
+        let two = data_folder(&root.join("backups/two"));
+        let three = data_folder(&root.join("backups/2021/three"));
+        assert_eq!(find_nested_data_folders(root.path(), 0).unwrap(), Vec::<PathBuf>::new());
+        assert_eq!(find_nested_data_folders(root.path(), 1).unwrap(), vec![one.clone()]);
+        assert_eq!(find_nested_data_folders(root.path(), 2).unwrap(), vec![two.clone(), one.clone()]);
+        assert_eq!(find_nested_data_folders(root.path(), 6).unwrap(), vec![three, two, one]);
+    }
+
+    #[test]
+    fn stops_at_a_data_folder() {
+        let root = TempDir::new();
+        let data = data_folder(root.path());
+        // A character folder that happens to look like a data folder inside it.
+        data_folder(&root.join("Character/nested"));
+        assert_eq!(find_nested_data_folders(root.path(), 0).unwrap(), vec![data.clone()]);
+        assert_eq!(find_nested_data_folders(root.path(), 6).unwrap(), vec![data]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn does_not_follow_symbolic_links() {
+        let root = TempDir::new();
+        let elsewhere = TempDir::new();
+        data_folder(&elsewhere.join("data"));
+        std::os::unix::fs::symlink(elsewhere.path(), root.join("link")).unwrap();
+        assert!(find_nested_data_folders(root.path(), 6).unwrap().is_empty());
+    }
+}
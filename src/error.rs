@@ -5,18 +5,30 @@ use thiserror::Error as E;
 
 #[derive(E, Debug)]
 pub(crate) enum Error {
-    #[error("Output folder `{0}` already exists")]
+    #[error("Output `{0}` already exists")]
     OutputExists(PathBuf),
+    #[error("Output `{0}` is where its own idx would be written, give a log without an extension")]
+    OutputIsIndex(PathBuf),
+    #[error("Staging folder `{0}` from an earlier merge exists, remove it first or use --resume")]
+    StagingExists(PathBuf),
     #[error("Specify more than one input folder")]
     NotEnoughInputs,
-    #[error("Input folder `{0}` does not exist")]
+    #[error("Input `{0}` does not exist")]
     InputDoesNotExist(PathBuf),
     #[error("Input folder `{0}` is not a directory")]
     InputIsNotDirectory(PathBuf),
+    #[error("`{0}` is not a file")]
+    InputIsNotFile(PathBuf),
+    #[error("Unable to find the home folder, specify one with --home")]
+    NoHomeFolder,
     #[error("{0}")]
     BadTimeDiff(#[from] humantime::DurationError),
     #[error("{0}")]
     BadTimestamp(#[from] humantime::TimestampError),
+    #[error("The start of the range {0} must be before its end {1}")]
+    BadRange(chrono::NaiveDateTime, chrono::NaiveDateTime),
+    #[error("{0}")]
+    BadPattern(#[from] regex::Error),
     #[error("Unable to create directory `{0}` due to: {1}")]
     UnableToCreateDirectory(PathBuf, io::Error),
     #[error("Failed to parse a message due to: {0}")]
@@ -25,8 +37,36 @@ pub(crate) enum Error {
     UnableToOpenIndex(PathBuf, io::Error),
     #[error("Unable to open log `{0}` due to: {1}")]
     UnableToOpenLog(PathBuf, io::Error),
+    #[error("Unable to open journal `{0}` due to: {1}")]
+    UnableToOpenJournal(PathBuf, io::Error),
+    #[error("Unable to write `{0}` due to: {1}")]
+    UnableToWrite(PathBuf, io::Error),
+    #[error("Unable to copy `{0}` due to: {1}")]
+    UnableToCopy(PathBuf, io::Error),
     #[error("Unable to open directory `{0}` due to: {1}")]
     UnableToOpenDirectory(PathBuf, io::Error),
+    #[error("Unable to open aliases `{0}` due to: {1}")]
+    UnableToOpenAliases(PathBuf, io::Error),
+    #[error("Bad alias in `{0}` on line {1}, expected `old-name = new-name`")]
+    BadAlias(PathBuf, usize),
+    #[error("Bad tab title `{0}`, expected LOG=TITLE")]
+    BadTabTitle(String),
+    #[error("Bad tab conflict setting `{0}`, expected LOG=SETTING[,SETTING] using values of --prefer and --keep")]
+    BadTabConflict(String),
+    #[error("Bad pseudonym `{0}`, expected NAME or NAME=PSEUDONYM")]
+    BadPseudonym(String),
+    #[error("Unable to remove directory `{0}` due to: {1}")]
+    UnableToRemoveDirectory(PathBuf, io::Error),
+    #[error("Unable to move `{0}` to `{1}` due to: {2}")]
+    UnableToMove(PathBuf, PathBuf, io::Error),
+    #[error("Unable to start worker threads due to: {0}")]
+    UnableToStartThreads(rayon::ThreadPoolBuildError),
+    #[error("Verifying `{0}` failed: {1}")]
+    VerificationFailed(PathBuf, String),
+    #[error("Unable to handle interrupts due to: {0}")]
+    UnableToHandleInterrupts(ctrlc::Error),
+    #[error("Interrupted")]
+    Interrupted,
     #[error("Exiting with error. Check output.")]
     ExitingWithError
 }
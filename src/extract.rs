@@ -0,0 +1,139 @@
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use chrono::NaiveDateTime;
+use log::{info, trace, warn};
+
+use crate::args::ExtractArgs;
+use crate::{check_input_file, check_input_folder, list_logs, read_log_tab_name, Error, LogWriter, MergeOptions, Reader};
+
+/// Copies a log's messages within `from..to` into a new log, which is only
+/// created if there are any. Returns how many messages were copied.
+fn extract_log(
+    log: &Path,
+    output: PathBuf,
+    from: Option<NaiveDateTime>,
+    to: Option<NaiveDateTime>,
+    merge_options: &MergeOptions
+) -> Result<u64, Error> {
+    let tab_name = read_log_tab_name(log)?;
+    let reader = Reader::open(log, merge_options.mmap)?.bounded(from, to);
+    let mut writer: Option<LogWriter> = None;
+    for message in reader {
+        let message = message?;
+        if writer.is_none() {
+            writer = Some(LogWriter::create(output.clone(), tab_name.clone())?);
+        }
+        writer.as_mut().unwrap().write(message)?;
+    }
+    match writer {
+        Some(writer) => {
+            let messages = writer.messages;
+            writer.finish()?;
+            Ok(messages)
+        },
+        None => Ok(0),
+    }
+}
+
+/// Lists the logs in a character's folder, or in its logs folder if given
+/// that.
+fn character_logs(folder: &Path) -> Result<Vec<PathBuf>, Error> {
+    let logs_folder = if folder.join("logs").is_dir() { folder.join("logs") } else { folder.to_path_buf() };
+    list_logs(&logs_folder)
+}
+
+/// Copies the messages within a time range of a log, or of every tab of a
+/// character, into new logs.
+pub(crate) fn extract(args: ExtractArgs, merge_options: &MergeOptions) -> Result<(), Error> {
+    let from: Option<NaiveDateTime> = args.from.map(|ts| ts.into());
+    let to: Option<NaiveDateTime> = args.to.map(|ts| ts.into());
+    if let (Some(from), Some(to)) = (from, to) {
+        if from >= to {
+            return Err(Error::BadRange(from, to))
+        }
+    }
+    if !args.character {
+        check_input_file(&args.input)?;
+        if let Some(parent) = args.output.parent().filter(|p| !p.as_os_str().is_empty()) {
+            create_dir_all(parent).map_err(|e| Error::UnableToCreateDirectory(parent.to_path_buf(), e))?;
+        }
+        let messages = extract_log(&args.input, args.output.clone(), from, to, merge_options)?;
+        if messages == 0 {
+            warn!("{} has no messages in the range, nothing was written.", args.input.to_string_lossy());
+        } else {
+            info!("Extracted {} messages to {}.", messages, args.output.to_string_lossy());
+        }
+        return Ok(())
+    }
+
+    check_input_folder(&args.input)?;
+    create_dir_all(&args.output).map_err(|e| Error::UnableToCreateDirectory(args.output.clone(), e))?;
+    let mut tabs = 0;
+    let mut total = 0;
+    for log in character_logs(&args.input)? {
+        let output = args.output.join(log.file_name().unwrap());
+        let messages = extract_log(&log, output, from, to, merge_options)?;
+        trace!("Extracted {} messages from {}.", messages, log.to_string_lossy());
+        if messages > 0 {
+            tabs += 1;
+            total += messages;
+        }
+    }
+    info!("Extracted {} messages from {} tabs to {}.", total, tabs, args.output.to_string_lossy());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{at, merge_options, message, read_log, write_log, write_tab, TempDir};
+
+    fn args(input: PathBuf, output: PathBuf, from: Option<i64>, to: Option<i64>, character: bool) -> ExtractArgs {
+        ExtractArgs { input, output, from: from.map(|s| at(s).into()), to: to.map(|s| at(s).into()), character }
+    }
+
+    #[test]
+    fn extracts_up_to_but_not_including_to() {
+        let dir = TempDir::new();
+        let m = [message(0, "Alice", "Hi"), message(10, "Bob", "Hey"), message(20, "Alice", "Bye")];
+        write_log(&dir.join("bob"), "Bob", &m);
+        extract(args(dir.join("bob"), dir.join("out/bob"), Some(10), Some(20), false), &merge_options(&[])).unwrap();
+        assert_eq!(read_log(&dir.join("out/bob")), m[1..2]);
+        assert_eq!(read_log_tab_name(&dir.join("out/bob")).unwrap(), "Bob");
+    }
+
+    #[test]
+    fn writes_nothing_for_an_empty_range() {
+        let dir = TempDir::new();
+        write_log(&dir.join("bob"), "Bob", &[message(0, "Alice", "Hi")]);
+        extract(args(dir.join("bob"), dir.join("out/bob"), Some(10), None, false), &merge_options(&[])).unwrap();
+        assert!(!dir.join("out/bob").exists());
+        assert!(!dir.join("out/bob.idx").exists());
+    }
+
+    #[test]
+    fn extracts_a_character_from_its_folder_or_logs_folder() {
+        let dir = TempDir::new();
+        let bob = [message(0, "Alice", "Hi"), message(20, "Bob", "Hey")];
+        write_tab(&dir.join("data"), "Alice", "bob", &bob);
+        write_tab(&dir.join("data"), "Alice", "carol", &[message(30, "Carol", "Hello")]);
+        for (i, input) in [dir.join("data/Alice"), dir.join("data/Alice/logs")].into_iter().enumerate() {
+            let output = dir.join(format!("out{}", i));
+            extract(args(input, output.clone(), None, Some(30), true), &merge_options(&[])).unwrap();
+            assert_eq!(read_log(&output.join("bob")), bob);
+            // Nothing of carol's is in the range.
+            assert!(!output.join("carol").exists());
+        }
+    }
+
+    #[test]
+    fn rejects_a_range_that_ends_before_it_starts() {
+        let dir = TempDir::new();
+        write_log(&dir.join("bob"), "Bob", &[message(0, "Alice", "Hi")]);
+        for (from, to) in [(20, 10), (10, 10)] {
+            let result = extract(args(dir.join("bob"), dir.join("out/bob"), Some(from), Some(to), false), &merge_options(&[]));
+            assert!(matches!(result, Err(Error::BadRange(..))), "{}..{}", from, to);
+        }
+        assert!(!dir.join("out").exists());
+    }
+}
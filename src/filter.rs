@@ -0,0 +1,38 @@
+use crate::args::PatternArg;
+
+/// Which characters and tabs to collect.
+///
+/// Empty include lists allow everything. Excludes win over includes.
+#[derive(Debug, Default)]
+pub(crate) struct Filters {
+    pub(crate) characters: Vec<PatternArg>,
+    pub(crate) exclude_characters: Vec<PatternArg>,
+    pub(crate) tabs: Vec<PatternArg>,
+    pub(crate) exclude_tabs: Vec<PatternArg>,
+}
+
+fn allowed(includes: &[PatternArg], excludes: &[PatternArg], names: &[&str]) -> bool {
+    let matches = |patterns: &[PatternArg]| patterns.iter()
+        .any(|p| names.iter().any(|n| p.is_match(n)));
+    (includes.is_empty() || matches(includes)) && !matches(excludes)
+}
+
+impl Filters {
+    /// Checks the character's folder name and the name it is merged under.
+    pub(crate) fn character_allowed(&self, character_names: &[&str]) -> bool {
+        allowed(&self.characters, &self.exclude_characters, character_names)
+    }
+
+    /// If tabs are filtered, which requires reading the tab name from the idx.
+    pub(crate) fn filters_tabs(&self) -> bool {
+        !self.tabs.is_empty() || !self.exclude_tabs.is_empty()
+    }
+
+    /// Checks the log's file name and, if known, the tab name from its idx.
+    pub(crate) fn tab_allowed(&self, log_name: &str, tab_name: Option<&str>) -> bool {
+        match tab_name {
+            Some(tab_name) => allowed(&self.tabs, &self.exclude_tabs, &[log_name, tab_name]),
+            None => allowed(&self.tabs, &self.exclude_tabs, &[log_name]),
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+use log::warn;
+
+use crate::Error;
+
+/// Fails early on an input file that is missing or is not a file.
+pub(crate) fn check_input_file(path: &Path) -> Result<(), Error> {
+    if !path.exists() {
+        Err(Error::InputDoesNotExist(path.to_path_buf()))
+    } else if !path.is_file() {
+        Err(Error::InputIsNotFile(path.to_path_buf()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Fails early on an input folder that is missing or is not a directory.
+pub(crate) fn check_input_folder(path: &Path) -> Result<(), Error> {
+    if !path.exists() {
+        Err(Error::InputDoesNotExist(path.to_path_buf()))
+    } else if !path.is_dir() {
+        Err(Error::InputIsNotDirectory(path.to_path_buf()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Lists the logs in a folder, sorted by name. Logs without their idx are
+/// skipped with a warning since the idx has the tab's name.
+pub(crate) fn list_logs(folder: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut logs = Vec::new();
+    for entry in read_dir(folder).map_err(|e| Error::UnableToOpenDirectory(folder.to_path_buf(), e))? {
+        let path = entry.map_err(|e| Error::UnableToOpenDirectory(folder.to_path_buf(), e))?.path();
+        // Log files do not have a extension.
+        if path.extension().is_some() || !path.is_file() {
+            continue
+        }
+        if !path.with_extension("idx").exists() {
+            warn!("{:?} is missing its idx file and has been skipped", path);
+            continue
+        }
+        logs.push(path);
+    }
+    logs.sort();
+    Ok(logs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir, write};
+    use super::*;
+    use crate::test_util::{message, write_log, TempDir};
+
+    #[test]
+    fn lists_logs_with_an_idx() {
+        let dir = TempDir::new();
+        write_log(&dir.join("carol"), "carol", &[message(0, "Carol", "Hi")]);
+        write_log(&dir.join("bob"), "bob", &[message(0, "Bob", "Hi")]);
+        write(dir.join("no-idx"), "").unwrap();
+        write(dir.join("notes.txt"), "").unwrap();
+        create_dir(dir.join("folder")).unwrap();
+        write(dir.join("folder.idx"), "").unwrap();
+        assert_eq!(list_logs(dir.path()).unwrap(), vec![dir.join("bob"), dir.join("carol")]);
+    }
+
+    #[test]
+    fn checks_inputs() {
+        let dir = TempDir::new();
+        write(dir.join("file"), "").unwrap();
+        assert!(check_input_file(&dir.join("file")).is_ok());
+        assert!(matches!(check_input_file(dir.path()), Err(Error::InputIsNotFile(_))));
+        assert!(matches!(check_input_file(&dir.join("missing")), Err(Error::InputDoesNotExist(_))));
+        assert!(check_input_folder(dir.path()).is_ok());
+        assert!(matches!(check_input_folder(&dir.join("file")), Err(Error::InputIsNotDirectory(_))));
+        assert!(matches!(check_input_folder(&dir.join("missing")), Err(Error::InputDoesNotExist(_))));
+    }
+}
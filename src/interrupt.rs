@@ -0,0 +1,23 @@
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use log::warn;
+
+use crate::Error;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Catches Ctrl-C and termination so tabs being merged can finish. A second
+/// one exits right away.
+pub(crate) fn install_handler() -> Result<(), Error> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            process::exit(130);
+        }
+        warn!("Interrupted, finishing the tabs being merged. Interrupt again to exit now.");
+    }).map_err(Error::UnableToHandleInterrupts)
+}
+
+/// If no new work should be started.
+pub(crate) fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
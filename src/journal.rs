@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions, read_to_string, remove_file};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use crate::{Error, MergeOptions};
+
+const JOURNAL_NAME: &str = ".merge-journal";
+const VERIFIED: &str = "verified";
+const UNVERIFIED: &str = "unverified";
+
+/// Record of which tabs were fully merged into an output folder and from
+/// what sources, so an interrupted merge can pick up where it left off.
+///
+/// Each line is the character, log name, if the tab was verified and the
+/// fingerprint separated by tabs. A line is only written once its tab's log
+/// and idx were synced to disk.
+pub(crate) struct Journal {
+    path: PathBuf,
+    /// Fingerprint of each completed tab and if it was verified.
+    completed: HashMap<(String, String), (String, bool)>,
+    file: Mutex<File>,
+}
+
+impl Journal {
+    /// Opens the journal in the output folder. When resuming, tabs recorded
+    /// by an earlier run are read, otherwise the journal is started over.
+    pub(crate) fn open(output_path: &Path, resume: bool) -> Result<Self, Error> {
+        let path = output_path.join(JOURNAL_NAME);
+        let mut completed = HashMap::new();
+        if resume && path.exists() {
+            let contents = read_to_string(&path)
+                .map_err(|e| Error::UnableToOpenJournal(path.clone(), e))?;
+            // A line cut short by a crash is missing fields and is ignored.
+            for line in contents.lines() {
+                let mut fields = line.splitn(4, '\t');
+                if let (Some(character), Some(log_name), Some(verified), Some(fingerprint)) = (fields.next(), fields.next(), fields.next(), fields.next()) {
+                    completed.insert((character.to_owned(), log_name.to_owned()), (fingerprint.to_owned(), verified == VERIFIED));
+                }
+            }
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(resume)
+            .write(true)
+            .truncate(!resume)
+            .open(&path)
+            .map_err(|e| Error::UnableToOpenJournal(path.clone(), e))?;
+        Ok(Self { path, completed, file: Mutex::new(file) })
+    }
+
+    /// Identifies a tab's sources by their paths, sizes and modification
+    /// times, in order, along with the options that change how it is merged.
+    /// A tab merged with other options is merged again when resuming.
+    pub(crate) fn fingerprint(log_name: &str, locations: &[PathBuf], merge_options: &MergeOptions) -> Result<String, Error> {
+        let mut parts = Vec::with_capacity(locations.len() + 1);
+        for p in locations {
+            let metadata = p.metadata().map_err(|e| Error::UnableToOpenLog(p.clone(), e))?;
+            let modified = metadata.modified()
+                .map_err(|e| Error::UnableToOpenLog(p.clone(), e))?
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos())
+                .unwrap_or(0);
+            parts.push(format!("{}:{}:{}", p.to_string_lossy(), metadata.len(), modified));
+        }
+        // Debug formatting escapes any tabs or newlines in a tab title.
+        parts.push(format!("{:?}", (
+            merge_options.time_diff.num_milliseconds(),
+            merge_options.max_window,
+            merge_options.out_of_order,
+            merge_options.reorder_window,
+            merge_options.shortcuts,
+            merge_options.fast_forward,
+            merge_options.since,
+            merge_options.until,
+            merge_options.tab_name_rule,
+            merge_options.tab_titles.get(log_name),
+            merge_options.conflict_for(log_name),
+        )));
+        Ok(parts.join("|"))
+    }
+
+    /// If an earlier run merged the tab from the same sources, and verified
+    /// it when it has to be.
+    pub(crate) fn is_complete(&self, character_name: &str, log_name: &str, fingerprint: &str, verify: bool) -> bool {
+        match self.completed.get(&(character_name.to_owned(), log_name.to_owned())) {
+            Some((completed, verified)) => completed == fingerprint && (*verified || !verify),
+            None => false,
+        }
+    }
+
+    pub(crate) fn record(&self, character_name: &str, log_name: &str, fingerprint: &str, verified: bool) -> Result<(), Error> {
+        let verified = if verified { VERIFIED } else { UNVERIFIED };
+        let line = format!("{}\t{}\t{}\t{}\n", character_name, log_name, verified, fingerprint);
+        self.file.lock().unwrap()
+            .write_all(line.as_bytes())
+            .map_err(|e| Error::UnableToWrite(self.path.clone(), e))
+    }
+
+    /// Removes the journal once the merge is done with.
+    pub(crate) fn remove(self) -> Result<(), Error> {
+        drop(self.file);
+        match remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(Error::UnableToWrite(self.path, e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{merge_options, message, write_tab, TempDir};
+
+    #[test]
+    fn fingerprint_changes_with_merge_options() {
+        let dir = TempDir::new();
+        let locations = vec![write_tab(dir.path(), "alice", "bob", &[message(0, "Alice", "Hi")])];
+        let fingerprint = |extra: &[&str]| Journal::fingerprint("bob", &locations, &merge_options(extra)).unwrap();
+        let plain = fingerprint(&[]);
+        assert_eq!(plain, fingerprint(&["--mmap", "--dupe-warning"]));
+        for extra in [
+            &["-d", "5s"][..],
+            &["--since", "2020-01-01 00:00:00"],
+            &["--prefer", "longest-body"],
+            &["--keep", "intersection"],
+            &["--tab-conflict", "bob=newest-source"],
+            &["--tab-title", "bob=Bob"],
+            &["--out-of-order", "sort"],
+        ] {
+            assert_ne!(plain, fingerprint(extra), "{:?}", extra);
+        }
+        // Options for other tabs do not matter.
+        assert_eq!(plain, fingerprint(&["--tab-title", "carol=Carol"]));
+    }
+
+    #[test]
+    fn merges_unverified_tabs_again_when_verifying() {
+        let dir = TempDir::new();
+        let locations = vec![write_tab(dir.path(), "alice", "bob", &[message(0, "Alice", "Hi")])];
+        let fingerprint = Journal::fingerprint("bob", &locations, &merge_options(&[])).unwrap();
+        let journal = Journal::open(dir.path(), false).unwrap();
+        journal.record("alice", "bob", &fingerprint, false).unwrap();
+        journal.record("alice", "carol", &fingerprint, true).unwrap();
+        drop(journal);
+
+        let journal = Journal::open(dir.path(), true).unwrap();
+        assert!(journal.is_complete("alice", "bob", &fingerprint, false));
+        assert!(!journal.is_complete("alice", "bob", &fingerprint, true));
+        assert!(journal.is_complete("alice", "carol", &fingerprint, true));
+        assert!(!journal.is_complete("alice", "dave", &fingerprint, false));
+    }
+}
@@ -0,0 +1,40 @@
+use std::sync::{Condvar, Mutex};
+
+/// Counting semaphore limiting how much of something happens at once, such as
+/// tabs doing IO or files being open.
+pub(crate) struct Limit {
+    total: usize,
+    available: Mutex<usize>,
+    freed: Condvar,
+}
+
+/// Holds part of a limit until dropped.
+pub(crate) struct LimitGuard<'a> {
+    limit: &'a Limit,
+    count: usize,
+}
+
+impl Limit {
+    pub(crate) fn new(total: usize) -> Self {
+        Self { total, available: Mutex::new(total), freed: Condvar::new() }
+    }
+
+    /// Waits until `count` is available and takes it. Asking for more than the
+    /// total waits for all of it instead so it can not wait forever.
+    pub(crate) fn acquire(&self, count: usize) -> LimitGuard<'_> {
+        let count = count.min(self.total);
+        let mut available = self.available.lock().unwrap();
+        while *available < count {
+            available = self.freed.wait(available).unwrap();
+        }
+        *available -= count;
+        LimitGuard { limit: self, count }
+    }
+}
+
+impl Drop for LimitGuard<'_> {
+    fn drop(&mut self) {
+        *self.limit.available.lock().unwrap() += self.count;
+        self.limit.freed.notify_all();
+    }
+}
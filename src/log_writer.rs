@@ -0,0 +1,59 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use fchat3_log_lib::FChatWriter;
+use fchat3_log_lib::fchat_index::FChatIndex;
+use fchat3_log_lib::fchat_message::FChatMessage;
+use log::debug;
+
+use crate::Error;
+
+/// A log being written with its idx.
+pub(crate) struct LogWriter {
+    log_path: PathBuf,
+    idx_path: PathBuf,
+    w: FChatWriter,
+    log_buf: BufWriter<File>,
+    idx_buf: BufWriter<File>,
+    pub(crate) messages: u64,
+}
+
+impl LogWriter {
+    /// Creates a log and its idx for the tab, failing if either exists.
+    pub(crate) fn create(log_path: PathBuf, tab_name: String) -> Result<Self, Error> {
+        let idx_path = log_path.with_extension("idx");
+        for path in [&log_path, &idx_path] {
+            if path.exists() {
+                return Err(Error::OutputExists(path.clone()))
+            }
+        }
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        let mut idx_buf = BufWriter::new(options.open(&idx_path)
+            .map_err(|e| Error::UnableToOpenIndex(idx_path.clone(), e))?);
+        let log_buf = BufWriter::new(options.open(&log_path)
+            .map_err(|e| Error::UnableToOpenLog(log_path.clone(), e))?);
+        let w = FChatWriter::new(&mut idx_buf, tab_name)?;
+        Ok(Self { log_path, idx_path, w, log_buf, idx_buf, messages: 0 })
+    }
+
+    pub(crate) fn write(&mut self, message: FChatMessage) -> Result<(), Error> {
+        self.w.write_message(&mut self.log_buf, &mut self.idx_buf, message)?;
+        self.messages += 1;
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> Result<(), Error> {
+        self.log_buf.flush().map_err(|e| Error::UnableToWrite(self.log_path.clone(), e))?;
+        self.idx_buf.flush().map_err(|e| Error::UnableToWrite(self.idx_path.clone(), e))?;
+        debug!("Wrote {} messages to {}.", self.messages, self.log_path.to_string_lossy());
+        Ok(())
+    }
+}
+
+/// Reads the tab name from a log's idx.
+pub(crate) fn read_log_tab_name(log: &Path) -> Result<String, Error> {
+    let idx_path = log.with_extension("idx");
+    let mut f = File::open(&idx_path).map_err(|e| Error::UnableToOpenIndex(idx_path.clone(), e))?;
+    Ok(FChatIndex::read_header_from_buf(&mut f)?.name)
+}
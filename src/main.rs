@@ -1,13 +1,11 @@
 use fchat3_log_lib::fchat_index::FChatIndex;
-use fchat3_log_lib::{FChatWriter, fchat_message::FChatMessage};
+use fchat3_log_lib::FChatWriter;
 use clap::Parser;
 use log::{error, trace, warn, info, debug};
-use std::cmp::Reverse;
-use std::collections::{BinaryHeap, HashMap};
+use std::collections::HashMap;
 use std::ffi::OsString;
-use std::fs::{File, OpenOptions, create_dir, create_dir_all, read_dir};
-use std::io::{BufWriter, BufReader};
-use std::iter::Peekable;
+use std::fs::{File, OpenOptions, create_dir, create_dir_all, read_dir, remove_dir_all, rename};
+use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::process;
 use chrono::{Duration, NaiveDateTime};
@@ -17,7 +15,7 @@ use linya::Progress;
 use humansize::{FormatSize, DECIMAL};
 
 mod args;
-pub(crate) use args::Args;
+pub(crate) use args::{Args, Command, Keep, OutOfOrder, Prefer, TabNameRule};
 
 mod error;
 pub(crate) use error::Error;
@@ -28,11 +26,139 @@ pub(crate) use sorted_message::SortedMessage;
 mod reader;
 pub(crate) use reader::Reader;
 
+mod discover;
+
+mod filter;
+pub(crate) use filter::Filters;
+
+mod aliases;
+pub(crate) use aliases::Aliases;
+
+mod dedupe;
+pub(crate) use dedupe::{Conflict, Resolver, deduplicate_messages};
+
+mod journal;
+pub(crate) use journal::Journal;
+
+mod interrupt;
+pub(crate) use interrupt::{install_handler as install_interrupt_handler, interrupted};
+
+mod verify;
+pub(crate) use verify::{WrittenTab, verify_tab};
+
+mod merge_files;
+pub(crate) use merge_files::merge_files;
+
+mod log_writer;
+pub(crate) use log_writer::{LogWriter, read_log_tab_name};
+
+mod input;
+pub(crate) use input::{check_input_file, check_input_folder, list_logs};
+
+mod split;
+pub(crate) use split::split;
+
+mod extract;
+pub(crate) use extract::extract;
+
+mod redact;
+pub(crate) use redact::redact;
+
+mod stats;
+pub(crate) use stats::stats;
+
+mod rename_character;
+pub(crate) use rename_character::rename_character;
+
+mod character_data;
+pub(crate) use character_data::copy_character_data;
+
+#[cfg(test)]
+mod test_util;
+
+mod limit;
+pub(crate) use limit::Limit;
+
+mod shortcut;
+pub(crate) use shortcut::{Containment, copy_tab, find_containing_source};
+
 type CharacterName = String;
 type LogName = String;
 type Logs = HashMap<LogName, Vec<PathBuf>>;
 type Characters = HashMap<CharacterName, Logs>;
 
+/// Settings shared by every tab being merged.
+pub(crate) struct MergeOptions {
+    pub(crate) time_diff: Duration,
+    /// Most kept messages held while looking for duplicates.
+    pub(crate) max_window: usize,
+    /// What to do with messages earlier than the one before them in a source.
+    pub(crate) out_of_order: OutOfOrder,
+    /// Most messages read ahead when sorting out of order messages.
+    pub(crate) reorder_window: usize,
+    pub(crate) dupe_warning: bool,
+    /// Copy tabs whose sources are the same or older cuts of one another.
+    pub(crate) shortcuts: bool,
+    /// Memory map logs instead of using buffered reads.
+    pub(crate) mmap: bool,
+    /// Check merged tabs after writing them.
+    pub(crate) verify: bool,
+    /// Most tabs being read or written at once.
+    pub(crate) io_jobs: Option<usize>,
+    /// Most files open at once while merging.
+    pub(crate) max_open_files: Option<usize>,
+    pub(crate) fast_forward: Option<NaiveDateTime>,
+    pub(crate) since: Option<NaiveDateTime>,
+    pub(crate) until: Option<NaiveDateTime>,
+    pub(crate) tab_name_rule: TabNameRule,
+    /// Explicit tab names by output log name.
+    pub(crate) tab_titles: HashMap<LogName, String>,
+    /// How duplicates are resolved unless overridden for a log.
+    pub(crate) conflict: Conflict,
+    pub(crate) tab_conflicts: HashMap<LogName, Conflict>,
+}
+
+impl MergeOptions {
+    fn new(args: &Args) -> Self {
+        let conflict = Conflict { prefer: args.prefer, keep: args.keep };
+        let mut tab_conflicts: HashMap<LogName, Conflict> = HashMap::new();
+        for tab_conflict in &args.tab_conflict {
+            let entry = tab_conflicts.entry(tab_conflict.0.clone()).or_insert(conflict);
+            if let Some(prefer) = tab_conflict.1 {
+                entry.prefer = prefer;
+            }
+            if let Some(keep) = tab_conflict.2 {
+                entry.keep = keep;
+            }
+        }
+
+        Self {
+            time_diff: args.time_diff.into(),
+            max_window: args.max_window,
+            out_of_order: args.out_of_order,
+            reorder_window: args.reorder_window,
+            dupe_warning: args.dupe_warning,
+            shortcuts: !args.always_parse,
+            mmap: args.mmap,
+            verify: args.verify,
+            io_jobs: args.io_jobs,
+            max_open_files: args.max_open_files,
+            fast_forward: args.fast_forward.map(|ts| ts.into()),
+            since: args.since.map(|ts| ts.into()),
+            until: args.until.map(|ts| ts.into()),
+            tab_name_rule: args.tab_name,
+            tab_titles: args.tab_title.iter().map(|t| (t.0.clone(), t.1.clone())).collect(),
+            conflict,
+            tab_conflicts,
+        }
+    }
+
+    /// How duplicates are resolved for a log.
+    pub(crate) fn conflict_for(&self, log_name: &str) -> Conflict {
+        self.tab_conflicts.get(log_name).copied().unwrap_or(self.conflict)
+    }
+}
+
 
 /*
     TODO: Add option to use the left-most log to find the time to skip to
@@ -50,40 +176,107 @@ fn main() {
 
 fn _main() -> Result<(), Error> {
     let args = Args::parse();
-    let fast_forward: Option<NaiveDateTime> = if let Some(ts) = args.fast_forward {
-        Some(ts.into())
-    } else {
-        None
-    };
     stderrlog::new()
         .module(module_path!())
         .verbosity(args.verbosity as usize + 2)
         .init()
         .unwrap();
+
+    let options = MergeOptions::new(&args);
+    if let (Some(since), Some(until)) = (options.since, options.until) {
+        if since >= until {
+            return Err(Error::BadRange(since, until))
+        }
+    }
+
+    match args.command {
+        Some(Command::Discover(discover_args)) => return discover::list_data_folders(discover_args.home),
+        Some(Command::MergeFiles(merge_files_args)) => return merge_files(merge_files_args, &options),
+        Some(Command::Split(split_args)) => return split(split_args, &options),
+        Some(Command::Extract(extract_args)) => return extract(extract_args, &options),
+        Some(Command::Redact(redact_args)) => return redact(redact_args, &options),
+        Some(Command::Stats(stats_args)) => return stats(stats_args, &options),
+        Some(Command::RenameCharacter(rename_args)) => return rename_character(rename_args, &options),
+        None => {}
+    }
+
+    let mut folders = args.folders;
+    if args.auto {
+        let home = match args.home {
+            Some(h) => h,
+            None => discover::home_folder()?,
+        };
+        let found = discover::find_data_folders(&home);
+        if found.is_empty() {
+            warn!("No F-Chat 3.0 data folders found in {}.", home.to_string_lossy());
+        }
+        for folder in found {
+            info!("Found data folder {}.", folder.to_string_lossy());
+            folders.push(folder);
+        }
+    }
     
-    let folder_paths  = args.folders
-        .into_iter()
-        .map(|p| {
-            // Fail early
-            if !p.exists() {
-                return Err(Error::InputDoesNotExist(p))
-            } else if !p.is_dir() {
-                return Err(Error::InputIsNotDirectory(p))
+    // Fail early
+    for p in &folders {
+        check_input_folder(p)?;
+    }
+
+    let folder_paths = if args.recursive {
+        let mut nested = Vec::new();
+        for folder in folders {
+            let found = discover::find_nested_data_folders(&folder, args.max_depth)?;
+            if found.is_empty() {
+                warn!("No data folders found in {} within {} folders.", folder.to_string_lossy(), args.max_depth);
             }
-            Ok(p)
-        })
-        .collect::<Result<Vec<_>,_>>()?;
-    
+            for data_folder in found {
+                info!("Found data folder {}.", data_folder.to_string_lossy());
+                nested.push(data_folder);
+            }
+        }
+        nested
+    } else {
+        folders
+    };
+
     if folder_paths.len() < 2 {
         return Err(Error::NotEnoughInputs)
     }
 
-    let (characters, size_total, file_total) = collect_logs(folder_paths)?;
+    let filters = Filters {
+        characters: args.characters,
+        exclude_characters: args.exclude_characters,
+        tabs: args.tabs,
+        exclude_tabs: args.exclude_tabs,
+    };
+
+    let mut character_aliases = Aliases::new(args.case_sensitive);
+    if let Some(path) = &args.character_aliases {
+        character_aliases.read_file(path)?;
+    }
+    let mut tab_aliases = Aliases::new(args.case_sensitive);
+    if let Some(path) = &args.tab_aliases {
+        tab_aliases.read_file(path)?;
+    }
+
+    let folder_order = folder_paths.clone();
+    let (characters, size_total, file_total) = collect_logs(
+        folder_paths,
+        &filters,
+        &character_aliases,
+        &tab_aliases
+    )?;
 
     info!("{} files to merge, {}.", file_total, size_total.format_size(DECIMAL));
     
     info!("Merging messages with at most a difference in the future of {}.", args.time_diff);
 
+    match (args.since, args.until) {
+        (Some(since), Some(until)) => info!("Only keeping messages from {} until {}.", since, until),
+        (Some(since), None) => info!("Only keeping messages from {} onwards.", since),
+        (None, Some(until)) => info!("Only keeping messages until {}.", until),
+        (None, None) => {}
+    }
+
     if args.dry_run {
         info!("Dry run enabled. Printing out what would be collected...");
         for (character, log_entries) in characters {
@@ -104,51 +297,123 @@ fn _main() -> Result<(), Error> {
         return Err(Error::OutputExists(output_path.to_owned()))
     }
 
-    create_dir(&output_path).map_err(|e| Error::UnableToCreateDirectory(output_path.clone(), e))?;
+    install_interrupt_handler()?;
 
+    if let Some(jobs) = args.jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()
+            .map_err(Error::UnableToStartThreads)?;
+    }
+
+    /* Merge into a sibling folder first so a failed merge never leaves a
+        half written output behind.
+    */
+    let staging_path = staging_path(&output_path);
+    if !staging_path.exists() {
+        create_dir(&staging_path).map_err(|e| Error::UnableToCreateDirectory(staging_path.clone(), e))?;
+    } else if args.resume {
+        info!("Resuming the merge in {}.", staging_path.to_string_lossy());
+    } else {
+        return Err(Error::StagingExists(staging_path))
+    }
+
+    let journal = match Journal::open(&staging_path, args.resume) {
+        Ok(journal) => journal,
+        Err(e) => {
+            discard_staging(&staging_path, args.resume, args.keep_partial)?;
+            return Err(e)
+        }
+    };
 
     let results: MergeResults = merge_logs(
         &characters,
-        &output_path,
-        args.time_diff.into(),
-        args.dupe_warning,
-        fast_forward
+        &staging_path,
+        &options,
+        &journal
     );
-    let mut character_index = 0;
     let mut error_count = 0;
-    for (character, log_entries) in characters {
+    let mut merged_count = 0;
+    let mut already_merged_count = 0;
+    let mut not_started_count = 0;
+    for (character_index, (character, log_entries)) in characters.iter().enumerate() {
         if let Err(e) = &results[character_index] {
             error_count += 1;
             error!("{} had an error: {}", character, e);
         }
-        let mut log_entry_index = 0;
-        for (log_name, _) in log_entries {
-            if let Err(e) = &results[character_index].as_ref().unwrap()[log_entry_index] {
-                error_count += 1;
-                error!("{} for {} had an error: {}", log_name, character, e);
+        if let Ok(log_results) = &results[character_index] {
+            let mut log_entry_index = 0;
+            for (log_name, _) in log_entries {
+                match &log_results[log_entry_index] {
+                    Ok(TabOutcome::Merged) => merged_count += 1,
+                    Ok(TabOutcome::AlreadyMerged) => already_merged_count += 1,
+                    Err(Error::Interrupted) => not_started_count += 1,
+                    Err(e) => {
+                        error_count += 1;
+                        error!("{} for {} had an error: {}", log_name, character, e);
+                    }
+                }
+                log_entry_index += 1;
             }
-            log_entry_index += 1;
         }
-        character_index += 1;
     }
-    return if error_count > 0 {
+    info!("{} tabs merged, {} already merged, {} not started, {} failed.",
+        merged_count, already_merged_count, not_started_count, error_count);
+    return if interrupted() {
+        info!("Partial output kept in {}, merge again with --resume to continue.", staging_path.to_string_lossy());
+        Err(Error::Interrupted)
+    } else if error_count > 0 {
         error!("{} errors were hit", error_count);
+        discard_staging(&staging_path, args.resume, args.keep_partial)?;
         Err(Error::ExitingWithError)
+    } else if let Err(e) = copy_character_data(&characters, &folder_order, &staging_path, args.character_data)
+        .and_then(|_| journal.remove())
+    {
+        discard_staging(&staging_path, args.resume, args.keep_partial)?;
+        Err(e)
     } else {
+        rename(&staging_path, &output_path)
+            .map_err(|e| Error::UnableToMove(staging_path.clone(), output_path.clone(), e))?;
         Ok(())
     }
 }
 
-fn collect_logs(folder_paths: Vec<PathBuf>) -> Result<(Characters, u64, u64), Error> {
+/// Removes the staging folder after a failed merge, unless it is kept to
+/// be resumed or looked at.
+fn discard_staging(staging_path: &Path, resume: bool, keep_partial: bool) -> Result<(), Error> {
+    if resume {
+        info!("Partial output kept in {}, merge again with --resume to continue.", staging_path.to_string_lossy());
+    } else if keep_partial {
+        info!("Partial output kept in {}.", staging_path.to_string_lossy());
+    } else {
+        remove_dir_all(staging_path)
+            .map_err(|e| Error::UnableToRemoveDirectory(staging_path.to_path_buf(), e))?;
+    }
+    Ok(())
+}
+
+/// Hidden folder next to the output that is merged into before being moved
+/// into place.
+fn staging_path(output_path: &Path) -> PathBuf {
+    let name = output_path.file_name().unwrap_or(output_path.as_os_str()).to_string_lossy();
+    output_path.with_file_name(format!(".{}.partial", name))
+}
+
+fn collect_logs(
+    folder_paths: Vec<PathBuf>,
+    filters: &Filters,
+    character_aliases: &Aliases,
+    tab_aliases: &Aliases
+) -> Result<(Characters, u64, u64), Error> {
     let mut characters = Characters::new();
+    // Compared name to the name used in `characters`.
+    let mut character_keys: HashMap<String, CharacterName> = HashMap::new();
+    // Same as above, but for logs within a character.
+    let mut log_keys: HashMap<(CharacterName, String), LogName> = HashMap::new();
     let mut size_total: u64 = 0;
     let mut file_total: u64 = 0;
     for folder_path in folder_paths {
-        if !folder_path.exists() {
-            return Err(Error::InputDoesNotExist(folder_path))
-        } else if !folder_path.is_dir() {
-            return Err(Error::InputIsNotDirectory(folder_path))
-        }
+        check_input_folder(&folder_path)?;
 
         let log_folders = read_dir(&folder_path)
             .map_err(|e| Error::UnableToOpenDirectory(folder_path, e))?
@@ -158,6 +423,13 @@ fn collect_logs(folder_paths: Vec<PathBuf>) -> Result<(Characters, u64, u64), Er
         for log_folder_entry in log_folders {
             let mut character_folder_path = log_folder_entry.path();
             let character_name = character_folder_path.file_name().unwrap().to_owned();
+            let folder_name = character_name.to_string_lossy();
+            let merged_name = character_aliases.resolve(&folder_name);
+
+            if !filters.character_allowed(&[&folder_name, merged_name]) {
+                trace!("Skipping filtered character {:?}", character_name);
+                continue;
+            }
 
             trace!("Getting logs for {:?}", character_name);
             character_folder_path.push("logs");
@@ -165,36 +437,45 @@ fn collect_logs(folder_paths: Vec<PathBuf>) -> Result<(Characters, u64, u64), Er
             if !character_folder_path.exists() { continue; }
 
             let mut logs: Vec<(OsString, PathBuf)> = Vec::new();
-            let log_files = read_dir(&character_folder_path)
-                .map_err(|e| Error::UnableToOpenDirectory(character_folder_path, e))?
-                .map(|e| e.unwrap())
-                // Log files do not have a extension.
-                .filter(|e| e.path().extension() == None )
-                // Check if an idx is present. Required to get correct tab name.
-                .filter(|e| {
-                    let mut p = e.path();
-                    p.set_extension("idx");
-                    if !p.exists() {
-                        warn!("{:?} is missing its idx file and has been skipped", p);
-                        false
-                    } else {
-                        true
+            for log_path in list_logs(&character_folder_path)? {
+                let log_name = log_path.file_name().unwrap().to_owned();
+                if filters.filters_tabs() {
+                    let mut idx_path = log_path.clone();
+                    idx_path.set_extension("idx");
+                    let mut f = File::open(&idx_path).map_err(|e| Error::UnableToOpenIndex(idx_path, e))?;
+                    let tab_name = FChatIndex::read_header_from_buf(&mut f)?.name;
+                    if !filters.tab_allowed(&log_name.to_string_lossy(), Some(&tab_name)) {
+                        trace!("-- {:?} ({}) filtered", log_name, tab_name);
+                        continue;
                     }
-                });
-        
-            for log_file_entry in log_files {
-                let log_name = log_file_entry.file_name();
-                size_total += log_file_entry.metadata()
-                    .map_err(|e| Error::UnableToOpenLog(log_file_entry.path(), e))?.len();
+                }
+                size_total += log_path.metadata()
+                    .map_err(|e| Error::UnableToOpenLog(log_path.clone(), e))?.len();
                 file_total += 1;
                 trace!("-- {:?}", log_name);
-                logs.push((log_name, log_file_entry.path()));
+                logs.push((log_name, log_path));
             }
         
             if logs.len() > 0 {
-                let character = characters.entry(character_name.to_string_lossy().into()).or_insert(Logs::new());
+                /* The first spelling seen is the one used for the output
+                    folder.
+                */
+                let key = character_keys.entry(character_aliases.key(merged_name))
+                    .or_insert_with(|| merged_name.to_owned())
+                    .clone();
+                if key != folder_name {
+                    debug!("Merging {:?} into {}", character_name, key);
+                }
+                let character = characters.entry(key.clone()).or_insert(Logs::new());
                 for (log_name, entry) in logs {
-                    character.entry(log_name.to_string_lossy().into()).or_insert(Vec::new()).push(entry);
+                    let log_name = log_name.to_string_lossy();
+                    let merged_log_name = tab_aliases.resolve(&log_name);
+                    let log_key = log_keys.entry((key.clone(), tab_aliases.key(merged_log_name)))
+                        .or_insert_with(|| merged_log_name.to_owned());
+                    if *log_key != log_name {
+                        debug!("Merging {:?} into {} for {}", log_name, log_key, key);
+                    }
+                    character.entry(log_key.clone()).or_insert(Vec::new()).push(entry);
                 }
             }
         }
@@ -202,21 +483,27 @@ fn collect_logs(folder_paths: Vec<PathBuf>) -> Result<(Characters, u64, u64), Er
     Ok((characters, size_total, file_total))
 }
 
-type PerLogMergeResults = Vec<Result<(), Error>>;
+/// What happened to a tab that did not fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TabOutcome {
+    Merged,
+    /// Merged by an earlier run that is being resumed.
+    AlreadyMerged,
+}
+
+type PerLogMergeResults = Vec<Result<TabOutcome, Error>>;
 type MergeResults = Vec<Result<PerLogMergeResults, Error>>;
 
 fn merge_logs(
     characters: &Characters,
     output_path: &Path,
-    time_diff: Duration,
-    dupe_warning: bool,
-    fast_forward: Option<NaiveDateTime>
+    merge_options: &MergeOptions,
+    journal: &Journal
 ) -> MergeResults {
     let progress = Mutex::new(Progress::new());
+    let io_limit = merge_options.io_jobs.map(Limit::new);
+    let file_limit = merge_options.max_open_files.map(Limit::new);
     characters.par_iter().map(|(character_name, log_entries)| {
-        let mut options = OpenOptions::new();
-        options.read(true).write(true).create(true);
-
         let mut output_log_location = output_path.to_path_buf();
         output_log_location.push(character_name.clone());
         output_log_location.push("logs");
@@ -235,207 +522,245 @@ fn merge_logs(
         );
 
         Ok(log_entries.par_iter().map(|(log_name, locations)| {
-            //info!("Merging tab {}", log_name.to_string_lossy());
-            let tab_name = {
-                let mut source_idx = locations[0].clone();
-                source_idx.set_extension("idx");
-
-                let mut f = File::open(&source_idx).map_err(|e| Error::UnableToOpenIndex(source_idx, e))?;
-                FChatIndex::read_header_from_buf(&mut f)?.name
+            let fingerprint = Journal::fingerprint(log_name, locations, merge_options)?;
+            let outcome = if journal.is_complete(character_name, log_name, &fingerprint, merge_options.verify) {
+                trace!("{} for {} was already merged, skipping.", log_name, character_name);
+                TabOutcome::AlreadyMerged
+            } else {
+                // Every source plus the merged log and idx.
+                let _open_files = file_limit.as_ref().map(|l| l.acquire(locations.len() + 2));
+                let _io = io_limit.as_ref().map(|l| l.acquire(1));
+                // Checked after waiting on the limits since that can take a while.
+                if interrupted() {
+                    return Err(Error::Interrupted)
+                }
+                let written = merge_tab(character_name, log_name, locations, &output_log_location, merge_options)?;
+                if merge_options.verify {
+                    verify_tab(&written, locations, merge_options.conflict_for(log_name), merge_options)?;
+                }
+                journal.record(character_name, log_name, &fingerprint, merge_options.verify)?;
+                TabOutcome::Merged
             };
+            progress.lock().unwrap().inc_and_draw(&bar.lock().unwrap(), 1);
+            Ok(outcome)
+        }).collect())
+    }).collect()
+}
 
-            let mut log_path = output_log_location.clone();
-            log_path.push(log_name);
-
-            let mut idx_path = log_path.clone();
-            idx_path.set_extension("idx");
-
-            let mut idx_buf = BufWriter::new(options.open(&idx_path)
-                .map_err(|e| Error::UnableToOpenIndex(idx_path, e))?);
-            let mut log_buf = BufWriter::new(options.open(&log_path)
-                .map_err(|e| Error::UnableToOpenLog(log_path, e))?);
-
-            let mut w = FChatWriter::new(
-                &mut idx_buf,
-                tab_name.clone()
-            )?;
+/// Merges a tab's sources into a log and idx in the character's output folder.
+fn merge_tab(
+    character_name: &str,
+    log_name: &str,
+    locations: &[PathBuf],
+    output_log_location: &Path,
+    merge_options: &MergeOptions
+) -> Result<WrittenTab, Error> {
+    let mut options = OpenOptions::new();
+    options.read(true).write(true).create(true).truncate(true);
+
+    //info!("Merging tab {}", log_name.to_string_lossy());
+    let tab_name = read_tab_name(log_name, locations, merge_options)?;
+
+    let mut log_path = output_log_location.to_path_buf();
+    log_path.push(log_name);
+
+    let mut idx_path = log_path.clone();
+    idx_path.set_extension("idx");
+
+    let mut idx_buf = BufWriter::new(options.open(&idx_path)
+        .map_err(|e| Error::UnableToOpenIndex(idx_path.clone(), e))?);
+    let mut log_buf = BufWriter::new(options.open(&log_path)
+        .map_err(|e| Error::UnableToOpenLog(log_path.clone(), e))?);
+
+    /* Sources that are copies or older cuts of another source have nothing
+        to add, so use the largest as-is instead of comparing messages. That
+        would keep out of order messages in place, so not when sorting them,
+        and the older cuts are missing messages, so not when intersecting.
+        Unlike merging, this keeps repeats within the source and does not
+        warn about its out of order messages, see the README.
+    */
+    let mut messages = Some(0);
+    let mut copied_from = None;
+    let conflict = merge_options.conflict_for(log_name);
+    let sorting = merge_options.out_of_order == OutOfOrder::Sort;
+    let intersecting = conflict.keep == Keep::Intersection;
+    let containing_source = if merge_options.shortcuts && !sorting && !intersecting && locations.len() > 1 {
+        find_containing_source(locations)?
+    } else {
+        None
+    };
 
-            let mut readers = Vec::with_capacity(locations.len());
-            for p in locations {
-                let file = File::open(p).map_err(|e| Error::UnableToOpenLog(p.into(), e))?;
-                readers.push(Reader::new(BufReader::new(file)).peekable())
+    if let Some((source, containment)) = containing_source {
+        match containment {
+            Containment::Identical => info!("{} for {} is identical in every source, using it as-is.",
+                log_name, character_name),
+            Containment::Prefix => info!("{} for {} is contained by {}, using it as-is.",
+                log_name, character_name, source.to_string_lossy()),
+            Containment::Messages => info!("{} for {} has every source's messages in {}, using it as-is.",
+                log_name, character_name, source.to_string_lossy()),
+        }
+        if merge_options.since.is_none() && merge_options.until.is_none() {
+            copy_tab(source, tab_name.clone(), &mut log_buf, &mut idx_buf)?;
+            messages = None;
+            copied_from = Some(source.clone());
+        } else {
+            let mut w = FChatWriter::new(&mut idx_buf, tab_name.clone())?;
+            let reader = Reader::open(source, merge_options.mmap)?
+                .bounded(merge_options.since, merge_options.until)
+                .ordered(merge_options.out_of_order, merge_options.reorder_window);
+            for message in reader {
+                w.write_message(&mut log_buf, &mut idx_buf, message?)?;
+                messages = messages.map(|n| n + 1);
             }
+        }
+    } else {
+        let mut w = FChatWriter::new(
+            &mut idx_buf,
+            tab_name.clone()
+        )?;
+
+        let mut readers = Vec::with_capacity(locations.len());
+        for p in locations {
+            readers.push(Reader::open(p, merge_options.mmap)?
+                .bounded(merge_options.since, merge_options.until)
+                .ordered(merge_options.out_of_order, merge_options.reorder_window)
+                .peekable())
+        }
 
-            // For single locations, just write them out without comparing.
-            if readers.len() == 1 {
-                for r in &mut readers[0] {
-                    let message = r?;
+        // For single locations, just write them out without comparing.
+        if readers.len() == 1 {
+            for r in &mut readers[0] {
+                let message = r?;
+                w.write_message(&mut log_buf, &mut idx_buf, message)?;
+                messages = messages.map(|n| n + 1);
+            }
+        // Otherwise open the files and get ready for the next step.
+        } else {
+            /* If we need to fast-forward, assume the left-most is correct
+                and write it's contents first then advance others.
+            */
+            if let Some(fast_forward_to) = merge_options.fast_forward {
+                let reader = &mut readers[0];
+                info!("Fast forwarding {} to {}...",
+                    character_name,
+                    fast_forward_to.format("%Y-%m-%d %H:%M:%S")
+                );
+                trace!("Writing left-most log for {}...", character_name);
+                while let Some(message) = match reader.peek() {
+                    Some(Ok(message)) if message.datetime <= fast_forward_to => Some(reader.next().unwrap().unwrap()),
+                    _ => None,
+                } {
                     w.write_message(&mut log_buf, &mut idx_buf, message)?;
+                    messages = messages.map(|n| n + 1);
                 }
-            // Otherwise open the files and get ready for the next step.
-            } else {
-                /* If we need to fast-forward, assume the left-most is correct
-                    and write it's contents first then advance others.
-                */
-                if let Some(fast_forward_to) = fast_forward {
-                    let reader = &mut readers[0];
-                    info!("Fast forwarding {} to {}...",
-                        character_name,
-                        fast_forward_to.format("%Y-%m-%d %H:%M:%S")
-                    );
-                    trace!("Writing left-most log for {}...", character_name);
-                    while let Some(message) = match reader.peek() {
-                        Some(Ok(message)) if message.datetime <= fast_forward_to => Some(reader.next().unwrap().unwrap()),
-                        _ => None,
-                    } {
-                        w.write_message(&mut log_buf, &mut idx_buf, message)?;
-                    }
-                    trace!("Advancing all other logs for {}...", character_name);
-                    // Fast forward all other logs...
-                    for index in 1..readers.len() {
-                        let reader = &mut readers[index];
-                        while match reader.peek() {
-                            Some(Ok(message)) if message.datetime <= fast_forward_to => true,
-                            _ => false,
-                        } { reader.next(); }
-                    }
-                    info!("Fast forward complete for {}.", character_name)
+                trace!("Advancing all other logs for {}...", character_name);
+                // Fast forward all other logs...
+                for index in 1..readers.len() {
+                    let reader = &mut readers[index];
+                    while match reader.peek() {
+                        Some(Ok(message)) if message.datetime <= fast_forward_to => true,
+                        _ => false,
+                    } { reader.next(); }
                 }
-                deduplicate_messages(readers, tab_name, time_diff, w, log_buf, idx_buf, &dupe_warning)?;
-
+                info!("Fast forward complete for {}.", character_name)
             }
-            progress.lock().unwrap().inc_and_draw(&bar.lock().unwrap(), 1);
-            Ok(())
-        }).collect())
-    }).collect()
-}
+            let resolver = Resolver::new(conflict, locations)?;
+            let written = deduplicate_messages(readers, tab_name.clone(), merge_options, &resolver, w, &mut log_buf, &mut idx_buf)?;
+            messages = messages.map(|n| n + written);
+        }
+    }
 
-fn format_message(message: &FChatMessage) -> String {
-    use fchat3_log_lib::fchat_message::FChatMessageType::*;
-    format!("[{}] {}", message.datetime,
-    match &message.body {
-        Message(m)  => format!("{}: {}",   message.sender, m),
-        Action(m)   => format!("{}{}",     message.sender, m),
-        Ad(m)       => format!("{}^ {}",   message.sender, m),
-        Roll(m)     => format!("* {}{}",   message.sender, m),
-        Warn(m)     => format!("! {}: {}", message.sender, m),
-        Event(m)    => format!("? {}{}",   message.sender, m),
-    })
-    
+    /* Synced rather than only flushed, as the tab is recorded in the
+        journal next and skipped when resuming after a crash.
+    */
+    log_buf.flush().and_then(|_| log_buf.get_ref().sync_all())
+        .map_err(|e| Error::UnableToWrite(log_path.clone(), e))?;
+    idx_buf.flush().and_then(|_| idx_buf.get_ref().sync_all())
+        .map_err(|e| Error::UnableToWrite(idx_path, e))?;
+    Ok(WrittenTab { log_path, tab_name, messages, copied_from })
 }
 
-fn deduplicate_messages(
-    mut readers: Vec<Peekable<Reader>>,
-    tab_name: String,
-    time_diff: Duration,
-    mut w: FChatWriter,
-    mut log_buf: BufWriter<File>,
-    mut idx_buf: BufWriter<File>,
-    dupe_warning: &bool,
-) -> Result<(), Error> {
-    let mut messages = BinaryHeap::new();
-    loop {
-        match messages.peek() {
-            None => {
-                /* The queue is empty and it needs an entry. Index and peek
-                    through all readers that have not reached EOF, find which
-                    one has the oldest entry, and *actually* read then push into
-                    the messages collection.
-
-                    If all are EOF, then we have finished.
-                */
-                let mut index = 0;
-                let mut sorted = Vec::with_capacity(readers.len());
-                while index < readers.len() {
-                    let reader = &mut readers[index];
-                    match reader.peek() {
-                        Some(Ok(message)) => {
-                            sorted.push((index, message.datetime));
-                            index += 1;
-                        },
-                        Some(Err(_)) => {
-                            debug!("Reader suffered an error while populating the queue.");
-                            let _ = reader.next().unwrap()?;
-                            panic!("We were expecting an error to unwrap, but it did not.");
-                        }
-                        None => {
-                            debug!("Discarding a empty reader.");
-                            let _ = readers.remove(index);
-                        }
-                    }
-                }
-                sorted.sort_by(|a, b| {
-                    a.1.partial_cmp(&b.1).unwrap()
-                });
-                if let Some((oldest_reader_index, _)) = sorted.first() {
-                    /* Double unwrap for the Some and Err. The above scan should
-                        confirm that we do have a message *and* it parsed.
-                    */
-                    let message = readers[*oldest_reader_index].next().unwrap().unwrap();
-                    messages.push(Reverse(SortedMessage(message)));
-                } else {
-                    trace!("finished {}", tab_name);
-                    break
-                }
-            },
-            Some(Reverse(SortedMessage(oldest_message))) => {
-                // Make a clone since the messages collection will be modified
-                let oldest_message_datetime = oldest_message.datetime.clone();
-                let mut index = 0;
-                while index < readers.len() {
-                    let reader = &mut readers[index];
-                    /* Readers too far in the future are skipped to prevent the
-                        message collection from getting too big but also the
-                        collection should always contain messages within the
-                        current time-diff.
-                    */
-                    match reader.peek() {
-                        Some(Ok(peeked_message))
-                        /* Even with a time-diff of 0, we should discard
-                            duplicate messages made at the same time to account
-                            for "syncing" an old log with an updated one.
-                        */
-                        if peeked_message.datetime > oldest_message_datetime + time_diff => {
-                            index += 1;
-                        },
-                        Some(Ok(_)) => {
-                            let check_message = reader.next().unwrap()?;
-                            let mut duplicate = false;
-                            let mut duplicate_hit = 0;
-                            for Reverse(SortedMessage(message)) in &messages {
-                                if check_message.sender == message.sender &&
-                                   check_message.body   == message.body
-                                {
-                                    trace!("Duplicate Hit:\n{}\n{}", format_message(&check_message), format_message(&message));
-                                    duplicate = true;
-                                    if *dupe_warning {
-                                        duplicate_hit += 1;
-                                        continue
-                                    } else {
-                                        break
-                                    }
-                                }
-                            }
-                            if !duplicate {
-                                messages.push(Reverse(SortedMessage(check_message)));
-                            } else if *dupe_warning && duplicate_hit > 0 {
-                                warn!("Message was duplicated {} times:\n{}", duplicate_hit, format_message(&check_message));
-                            }
-                        },
-                        Some(Err(_)) => {
-                            debug!("Reader suffered an error during comparisons.");
-                            reader.next().unwrap()?;
-                            unreachable!("We were expecting an error to unwrap, but it did not.");
-                        },
-                        None => {let _ = readers.remove(index);},
-                    }
+/// Gets the tab name for a merged log from the source picked by the tab name
+/// rule, unless one was explicitly given.
+fn read_tab_name(log_name: &str, locations: &[PathBuf], merge_options: &MergeOptions) -> Result<String, Error> {
+    if let Some(title) = merge_options.tab_titles.get(log_name) {
+        return Ok(title.clone())
+    }
+    let mut idx_paths = locations.iter().map(|p| p.with_extension("idx"));
+    let source_idx = match merge_options.tab_name_rule {
+        TabNameRule::First => idx_paths.next().unwrap(),
+        TabNameRule::Latest => {
+            let mut latest = None;
+            for idx_path in idx_paths {
+                let modified = idx_path.metadata()
+                    .and_then(|m| m.modified())
+                    .map_err(|e| Error::UnableToOpenIndex(idx_path.clone(), e))?;
+                match &latest {
+                    Some((latest_modified, _)) if *latest_modified >= modified => {},
+                    _ => latest = Some((modified, idx_path)),
                 }
-                let Reverse(SortedMessage(message)) = messages.pop().unwrap();
-                debug!("Message queue: {}", messages.len());
-                debug!("Committing message:\n[{}] {}", tab_name , format_message(&message));
-                w.write_message(&mut log_buf, &mut idx_buf, message)?;
             }
+            latest.unwrap().1
         }
+    };
+
+    let mut f = File::open(&source_idx).map_err(|e| Error::UnableToOpenIndex(source_idx, e))?;
+    Ok(FChatIndex::read_header_from_buf(&mut f)?.name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+    use super::*;
+    use crate::test_util::{message, write_tab, TempDir};
+
+    fn tab(data: &Path, character: &str, log_name: &str) -> PathBuf {
+        write_tab(data, character, log_name, &[message(0, character, "Hi")])
     }
-    Ok(())
-}
\ No newline at end of file
+
+    #[test]
+    fn collects_characters_and_tabs_under_one_name() {
+        let dir = TempDir::new();
+        let (a, b) = (dir.join("a"), dir.join("b"));
+        let a_bob = tab(&a, "Some Name", "bob");
+        let a_channel = tab(&a, "Some Name", "new-channel");
+        let b_bob = tab(&b, "some name", "Bob");
+        let b_channel = tab(&b, "some name", "old-channel");
+        let b_dave = tab(&b, "Old Name", "dave");
+        tab(&b, "Other", "bob");
+        write(dir.join("characters"), "Old Name = SOME NAME\n").unwrap();
+        write(dir.join("tabs"), "old-channel = new-channel\n").unwrap();
+
+        let mut character_aliases = Aliases::new(false);
+        character_aliases.read_file(&dir.join("characters")).unwrap();
+        let mut tab_aliases = Aliases::new(false);
+        tab_aliases.read_file(&dir.join("tabs")).unwrap();
+        // Old Name is only allowed through the name it is merged under.
+        let filters = Filters { characters: vec!["some name".parse().unwrap()], ..Filters::default() };
+        let (characters, _, files) = collect_logs(vec![a, b], &filters, &character_aliases, &tab_aliases).unwrap();
+
+        // The first spelling found names the character and its tabs.
+        assert_eq!(characters.keys().collect::<Vec<_>>(), vec!["Some Name"]);
+        let mut logs: Vec<_> = characters["Some Name"].iter().collect();
+        logs.sort();
+        assert_eq!(logs, vec![
+            (&"bob".to_owned(), &vec![a_bob, b_bob]),
+            (&"dave".to_owned(), &vec![b_dave]),
+            (&"new-channel".to_owned(), &vec![a_channel, b_channel]),
+        ]);
+        assert_eq!(files, 5);
+    }
+
+    #[test]
+    fn keeps_names_that_differ_by_case_apart_when_case_sensitive() {
+        let dir = TempDir::new();
+        let (a, b) = (dir.join("a"), dir.join("b"));
+        tab(&a, "Some Name", "bob");
+        tab(&b, "some name", "Bob");
+        let aliases = Aliases::new(true);
+        let (characters, _, _) = collect_logs(vec![a, b], &Filters::default(), &aliases, &aliases).unwrap();
+        let mut names: Vec<_> = characters.iter().map(|(c, logs)| (c.as_str(), logs.keys().cloned().collect::<Vec<_>>())).collect();
+        names.sort();
+        assert_eq!(names, vec![("Some Name", vec!["bob".to_owned()]), ("some name", vec!["Bob".to_owned()])]);
+    }
+}
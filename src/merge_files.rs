@@ -0,0 +1,107 @@
+use std::fs::{create_dir_all, remove_file};
+use std::path::Path;
+use log::{info, warn};
+
+use crate::args::MergeFilesArgs;
+use crate::{check_input_file, merge_tab, verify_tab, Error, MergeOptions};
+
+/// Merges logs given directly into one log, for stray logs that are not in a
+/// data folder.
+pub(crate) fn merge_files(args: MergeFilesArgs, merge_options: &MergeOptions) -> Result<(), Error> {
+    for file in &args.files {
+        check_input_file(file)?;
+    }
+
+    /* Both are checked before anything is written, as a failed merge
+        removes whatever is at either path.
+    */
+    let output = args.output;
+    let output_idx = output.with_extension("idx");
+    if output_idx == output {
+        return Err(Error::OutputIsIndex(output))
+    }
+    for path in [&output, &output_idx] {
+        if path.exists() {
+            return Err(Error::OutputExists(path.clone()))
+        }
+    }
+    let log_name = match output.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => return Err(Error::InputIsNotFile(output)),
+    };
+    let output_folder = match output.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => Path::new(".").to_path_buf(),
+    };
+    create_dir_all(&output_folder)
+        .map_err(|e| Error::UnableToCreateDirectory(output_folder.clone(), e))?;
+
+    info!("Merging {} files into {}.", args.files.len(), output.to_string_lossy());
+    let merged = merge_tab("the given files", &log_name, &args.files, &output_folder, merge_options)
+        .and_then(|written| {
+            if merge_options.verify {
+                verify_tab(&written, &args.files, merge_options.conflict_for(&log_name), merge_options)?;
+            }
+            Ok(written)
+        });
+
+    match merged {
+        Ok(written) => {
+            match written.messages {
+                Some(messages) => info!("Wrote {} messages to {}.", messages, output.to_string_lossy()),
+                None => info!("Copied {} as-is.", output.to_string_lossy()),
+            }
+            Ok(())
+        },
+        Err(e) => {
+            // Don't leave a half written log behind that would be mistaken for a merged one.
+            for path in [output.clone(), output_idx] {
+                if path.exists() {
+                    if let Err(e) = remove_file(&path) {
+                        warn!("Unable to remove {}: {}", path.to_string_lossy(), e);
+                    }
+                }
+            }
+            Err(e)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{read, write};
+    use super::*;
+    use crate::test_util::{merge_options, message, read_log, write_log, TempDir};
+
+    fn args(dir: &TempDir, output: &str) -> MergeFilesArgs {
+        let files = vec![dir.join("a"), dir.join("b")];
+        write_log(&files[0], "bob", &[message(0, "Alice", "Hi")]);
+        write_log(&files[1], "bob", &[message(10, "Bob", "Hey")]);
+        MergeFilesArgs { files, output: dir.join(output) }
+    }
+
+    #[test]
+    fn merges_the_files() {
+        let dir = TempDir::new();
+        merge_files(args(&dir, "merged/bob"), &merge_options(&[])).unwrap();
+        assert_eq!(read_log(&dir.join("merged/bob")), vec![message(0, "Alice", "Hi"), message(10, "Bob", "Hey")]);
+    }
+
+    #[test]
+    fn refuses_an_existing_idx() {
+        let dir = TempDir::new();
+        let args = args(&dir, "bob");
+        write(dir.join("bob.idx"), "not merged").unwrap();
+        assert!(matches!(merge_files(args, &merge_options(&[])), Err(Error::OutputExists(_))));
+        assert_eq!(read(dir.join("bob.idx")).unwrap(), b"not merged");
+        assert!(!dir.join("bob").exists());
+    }
+
+    #[test]
+    fn refuses_an_output_named_like_its_idx() {
+        let dir = TempDir::new();
+        let args = args(&dir, "bob.idx");
+        assert!(matches!(merge_files(args, &merge_options(&[])), Err(Error::OutputIsIndex(_))));
+        assert!(!dir.join("bob.idx").exists());
+    }
+}
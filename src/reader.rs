@@ -1,17 +1,129 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+use std::path::{Path, PathBuf};
+use chrono::NaiveDateTime;
+use log::{debug, warn};
+use memmap2::Mmap;
 use fchat3_log_lib::read_fchatmessage_from_buf;
 use fchat3_log_lib::fchat_message::FChatMessage;
 use fchat3_log_lib::ReadSeek;
 
-use crate::Error;
+use crate::{Error, OutOfOrder, SortedMessage};
 
 
 pub(crate) struct Reader<'a> {
-    pub(crate) buf: Box<dyn ReadSeek + 'a>
+    pub(crate) buf: Box<dyn ReadSeek + 'a>,
+    /// Messages before this are skipped.
+    pub(crate) since: Option<NaiveDateTime>,
+    /// Messages at or after this are skipped.
+    pub(crate) until: Option<NaiveDateTime>,
+    /// Log being read, for warnings.
+    path: Option<PathBuf>,
+    ordering: OutOfOrder,
+    /// Most messages held while sorting.
+    reorder_window: usize,
+    /// Messages read ahead while sorting.
+    reorder: BinaryHeap<Reverse<SortedMessage>>,
+    read: u64,
+    finished: bool,
+    /// Latest time yielded so far.
+    latest: Option<NaiveDateTime>,
+    /// Messages yielded that were earlier than one before them.
+    out_of_order: u64,
+    /// Don't warn about out of order messages, such as when re-reading a log.
+    quiet: bool,
 }
 
 impl<'a> Reader<'a> {
     pub(crate) fn new<T: 'a + ReadSeek>(stream: T) -> Self {
-        Self { buf: Box::new(stream) }
+        Self {
+            buf: Box::new(stream),
+            since: None,
+            until: None,
+            path: None,
+            ordering: OutOfOrder::Preserve,
+            reorder_window: 1,
+            reorder: BinaryHeap::new(),
+            read: 0,
+            finished: false,
+            latest: None,
+            out_of_order: 0,
+            quiet: false,
+        }
+    }
+
+    /// Opens a log, memory mapping it if asked to. Falls back to buffered
+    /// reads if the log can not be mapped. Mapping only saves the read calls,
+    /// messages are still parsed into owned strings.
+    pub(crate) fn open(path: &Path, mmap: bool) -> Result<Self, Error> {
+        let file = File::open(path).map_err(|e| Error::UnableToOpenLog(path.into(), e))?;
+        let mut reader = None;
+        if mmap {
+            /* SAFETY: The map is only read from. Logs being modified while
+                being merged, such as by a running client, is no more
+                supported than with buffered reads.
+            */
+            match unsafe { Mmap::map(&file) } {
+                Ok(map) => reader = Some(Self::new(Cursor::new(map))),
+                Err(e) => debug!("Unable to map {:?}, falling back to buffered reads: {}", path, e),
+            }
+        }
+        let mut reader = reader.unwrap_or_else(|| Self::new(BufReader::new(file)));
+        reader.path = Some(path.to_path_buf());
+        Ok(reader)
+    }
+
+    /// Only yield messages within `since..until`.
+    pub(crate) fn bounded(mut self, since: Option<NaiveDateTime>, until: Option<NaiveDateTime>) -> Self {
+        self.since = since;
+        self.until = until;
+        self
+    }
+
+    /// Handle messages earlier than the one before them as asked to. When
+    /// sorting, up to `reorder_window` messages are read ahead.
+    pub(crate) fn ordered(mut self, ordering: OutOfOrder, reorder_window: usize) -> Self {
+        self.ordering = ordering;
+        self.reorder_window = reorder_window;
+        self
+    }
+
+    pub(crate) fn quiet(mut self) -> Self {
+        self.quiet = true;
+        self
+    }
+
+    fn in_bounds(&self, message: &FChatMessage) -> bool {
+        !matches!(self.since, Some(since) if message.datetime < since) &&
+        !matches!(self.until, Some(until) if message.datetime >= until)
+    }
+
+    fn read_message(&mut self) -> Option<Result<FChatMessage, Error>> {
+        loop {
+            match read_fchatmessage_from_buf(&mut self.buf) {
+                Ok(Some(m)) if !self.in_bounds(&m) => continue,
+                Ok(Some(m)) => return Some(Ok(m)),
+                Ok(None) => return None,
+                Err(e) => return Some(Err(Error::from(e))),
+            }
+        }
+    }
+
+    /// Warns once about messages that were yielded out of order.
+    fn warn_out_of_order(&mut self) {
+        if self.out_of_order == 0 || self.quiet || self.ordering == OutOfOrder::Preserve {
+            return
+        }
+        let path = self.path.as_deref().map(Path::to_string_lossy).unwrap_or_default();
+        match self.ordering {
+            OutOfOrder::Sort => warn!("{} has {} messages too far out of order to sort within {} messages.",
+                path, self.out_of_order, self.reorder_window),
+            _ => warn!("{} has {} messages earlier than one before them, keeping the source's order.",
+                path, self.out_of_order),
+        }
+        self.out_of_order = 0;
     }
 }
 
@@ -19,10 +131,36 @@ impl Iterator for Reader<'_> {
     type Item = Result<FChatMessage, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match read_fchatmessage_from_buf(&mut self.buf) {
-            Ok(Some(m)) => Some(Ok(m)),
-            Ok(None) => None,
-            Err(e) => Some(Err(Error::from(e))),
+        let message = if self.ordering == OutOfOrder::Sort {
+            while !self.finished && self.reorder.len() < self.reorder_window {
+                match self.read_message() {
+                    Some(Ok(m)) => {
+                        self.reorder.push(Reverse(SortedMessage::new(m, 0, self.read)));
+                        self.read += 1;
+                    },
+                    Some(Err(e)) => return Some(Err(e)),
+                    None => self.finished = true,
+                }
+            }
+            self.reorder.pop().map(|Reverse(sorted)| Ok(sorted.message))
+        } else {
+            self.read_message()
+        };
+
+        match message {
+            Some(Ok(m)) => {
+                if matches!(self.latest, Some(latest) if m.datetime < latest) {
+                    self.out_of_order += 1;
+                } else {
+                    self.latest = Some(m.datetime);
+                }
+                Some(Ok(m))
+            },
+            None => {
+                self.warn_out_of_order();
+                None
+            },
+            e => e,
         }
     }
 }
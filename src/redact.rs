@@ -0,0 +1,301 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{create_dir_all, read, write};
+use std::path::{Path, PathBuf};
+use fchat3_log_lib::fchat_message::FChatMessage;
+use log::{debug, info};
+use regex::{Captures, Regex, RegexBuilder};
+
+use crate::args::RedactArgs;
+use crate::{check_input_file, read_log_tab_name, Error, LogWriter, MergeOptions, Reader};
+
+/// Rewrites names and text in messages. Names are matched case-insensitively
+/// as F-Chat names are.
+struct Redactor {
+    /// Lowercased name to its pseudonym.
+    pseudonyms: HashMap<String, String>,
+    /// Matches any name being pseudonymized as a whole word.
+    names: Option<Regex>,
+    /// Lowercased names whose messages are removed.
+    removed: HashSet<String>,
+    masks: Vec<Regex>,
+    mask_with: String,
+}
+
+impl Redactor {
+    fn new(args: &RedactArgs) -> Result<Self, Error> {
+        let mut pseudonyms = HashMap::new();
+        // Only names without a chosen pseudonym are numbered.
+        let mut unnamed = 0;
+        for arg in &args.pseudonymize {
+            let pseudonym = match &arg.1 {
+                Some(pseudonym) => pseudonym.clone(),
+                None => {
+                    unnamed += 1;
+                    format!("Person {}", unnamed)
+                },
+            };
+            pseudonyms.insert(arg.0.to_lowercase(), pseudonym);
+        }
+
+        // Longest first so a name containing another is replaced whole.
+        let mut names: Vec<&String> = pseudonyms.keys().collect();
+        names.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+        let names = if names.is_empty() {
+            None
+        } else {
+            let alternatives: Vec<String> = names.iter().map(|name| {
+                // Names can start or end with characters `\b` does not work next to.
+                let boundary = |c: Option<char>| if matches!(c, Some(c) if c.is_alphanumeric() || c == '_') { r"\b" } else { "" };
+                format!("{}{}{}", boundary(name.chars().next()), regex::escape(name), boundary(name.chars().last()))
+            }).collect();
+            Some(RegexBuilder::new(&alternatives.join("|")).case_insensitive(true).build()?)
+        };
+
+        Ok(Self {
+            pseudonyms,
+            names,
+            removed: args.remove_sender.iter().map(|n| n.to_lowercase()).collect(),
+            masks: args.mask.clone(),
+            mask_with: args.mask_with.clone(),
+        })
+    }
+
+    fn is_removed(&self, sender: &str) -> bool {
+        self.removed.contains(&sender.to_lowercase())
+    }
+
+    /// Replaces the names being pseudonymized in some text.
+    fn replace_names(&self, text: &str) -> String {
+        match &self.names {
+            Some(names) => names.replace_all(text, |c: &Captures| {
+                self.pseudonyms.get(&c[0].to_lowercase()).cloned().unwrap_or_else(|| c[0].to_owned())
+            }).into_owned(),
+            None => text.to_owned(),
+        }
+    }
+
+    /// Pseudonymizes names in and masks some text.
+    fn redact_text(&self, text: &str) -> String {
+        let mut text = self.replace_names(text);
+        for mask in &self.masks {
+            text = mask.replace_all(&text, self.mask_with.as_str()).into_owned();
+        }
+        text
+    }
+
+    /// Redacts a message, or returns `None` if it is removed.
+    fn redact_message(&self, message: FChatMessage) -> Option<FChatMessage> {
+        use fchat3_log_lib::fchat_message::FChatMessageType::*;
+        if self.is_removed(&message.sender) {
+            return None
+        }
+        let body = match &message.body {
+            Message(m) => Message(self.redact_text(m)),
+            Action(m)  => Action(self.redact_text(m)),
+            Ad(m)      => Ad(self.redact_text(m)),
+            Roll(m)    => Roll(self.redact_text(m)),
+            Warn(m)    => Warn(self.redact_text(m)),
+            Event(m)   => Event(self.redact_text(m)),
+        };
+        Some(FChatMessage {
+            datetime: message.datetime,
+            sender: self.replace_names(&message.sender),
+            body,
+        })
+    }
+
+    /// If the text after a line's time starts with a removed sender's name,
+    /// as messages do and actions do after a `*`.
+    fn is_removed_line(&self, rest: &str) -> bool {
+        let rest = rest.to_lowercase();
+        let rest = rest.strip_prefix('*').unwrap_or(&rest);
+        self.removed.iter().any(|name| {
+            rest.starts_with(name.as_str()) &&
+                !matches!(rest[name.len()..].chars().next(), Some(c) if c.is_alphanumeric() || c == '_')
+        })
+    }
+}
+
+/// Splits off the `[time] ` a line of an exported log starts with, returning
+/// the rest of the line. Lines without one continue the message before them.
+fn strip_time(line: &str) -> Option<&str> {
+    let (time, rest) = line.strip_prefix('[')?.split_once("] ")?;
+    let is_time = time.chars().any(|c| c.is_ascii_digit()) &&
+        time.chars().all(|c| c.is_ascii_digit() || " :-/.,APMapm".contains(c));
+    if is_time { Some(rest) } else { None }
+}
+
+/// Rewrites a log with its idx next to it, pseudonymizing its tab name too.
+/// Returns how many messages were kept and removed.
+fn redact_log(log: &Path, output: PathBuf, redactor: &Redactor, merge_options: &MergeOptions) -> Result<(u64, u64), Error> {
+    let tab_name = redactor.replace_names(&read_log_tab_name(log)?);
+    let mut writer = LogWriter::create(output, tab_name)?;
+    let mut removed = 0;
+    for message in Reader::open(log, merge_options.mmap)? {
+        match redactor.redact_message(message?) {
+            Some(message) => writer.write(message)?,
+            None => removed += 1,
+        }
+    }
+    let kept = writer.messages;
+    writer.finish()?;
+    Ok((kept, removed))
+}
+
+/// Rewrites an exported log line by line, removing messages from removed
+/// senders along with the lines they continue on. Returns how many lines
+/// were kept and removed.
+fn redact_export(export: &Path, output: PathBuf, redactor: &Redactor) -> Result<(u64, u64), Error> {
+    if output.exists() {
+        return Err(Error::OutputExists(output))
+    }
+    let contents = read(export).map_err(|e| Error::UnableToOpenLog(export.to_path_buf(), e))?;
+    let contents = String::from_utf8_lossy(&contents);
+    let mut redacted = String::with_capacity(contents.len());
+    let (mut kept, mut removed) = (0, 0);
+    // If the message the lines belong to is from a removed sender.
+    let mut removing = false;
+    for line in contents.split_inclusive('\n') {
+        if let Some(rest) = strip_time(line) {
+            removing = redactor.is_removed_line(rest);
+        }
+        if removing {
+            removed += 1;
+            continue
+        }
+        redacted.push_str(&redactor.redact_text(line));
+        kept += 1;
+    }
+    write(&output, redacted).map_err(|e| Error::UnableToWrite(output, e))?;
+    Ok((kept, removed))
+}
+
+/// Writes redacted copies of logs to the output folder. Logs with an idx
+/// next to them are rewritten message by message, anything else is treated
+/// as an exported text log. Files named after a pseudonymized name, such as
+/// private messages, are renamed too.
+pub(crate) fn redact(args: RedactArgs, merge_options: &MergeOptions) -> Result<(), Error> {
+    for file in &args.files {
+        check_input_file(file)?;
+    }
+    let redactor = Redactor::new(&args)?;
+    create_dir_all(&args.output).map_err(|e| Error::UnableToCreateDirectory(args.output.clone(), e))?;
+
+    for file in &args.files {
+        let file_name = file.file_name().unwrap().to_string_lossy();
+        let output = args.output.join(match redactor.pseudonyms.get(&file_name.to_lowercase()) {
+            Some(pseudonym) => pseudonym.to_lowercase(),
+            None => file_name.into_owned(),
+        });
+        let is_log = file.extension().is_none() && file.with_extension("idx").exists();
+        let (kept, removed) = if is_log {
+            redact_log(file, output.clone(), &redactor, merge_options)?
+        } else {
+            redact_export(file, output.clone(), &redactor)?
+        };
+        debug!("Removed {} {} from {}.", removed, if is_log { "messages" } else { "lines" }, file.to_string_lossy());
+        info!("Redacted {} to {} with {} {}.",
+            file.to_string_lossy(),
+            output.to_string_lossy(),
+            kept,
+            if is_log { "messages" } else { "lines" }
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+    use super::*;
+    use crate::test_util::{merge_options, message, read_log, write_log, TempDir};
+
+    fn redact_text_export(contents: &str) -> String {
+        let dir = TempDir::new();
+        let args = RedactArgs {
+            files: Vec::new(),
+            output: dir.join("output"),
+            pseudonymize: Vec::new(),
+            remove_sender: vec!["Other Person".to_owned()],
+            mask: Vec::new(),
+            mask_with: "[redacted]".to_owned(),
+        };
+        write(dir.join("export.txt"), contents).unwrap();
+        redact_export(&dir.join("export.txt"), dir.join("redacted.txt"), &Redactor::new(&args).unwrap()).unwrap();
+        read_to_string(dir.join("redacted.txt")).unwrap()
+    }
+
+    #[test]
+    fn numbers_names_without_a_chosen_pseudonym() {
+        let args = RedactArgs {
+            files: Vec::new(),
+            output: PathBuf::new(),
+            pseudonymize: ["Alice=Ally", "Bob", "Carol"].iter().map(|s| s.parse().unwrap()).collect(),
+            remove_sender: Vec::new(),
+            mask: Vec::new(),
+            mask_with: "[redacted]".to_owned(),
+        };
+        let redactor = Redactor::new(&args).unwrap();
+        assert_eq!(redactor.replace_names("Alice, Bob and Carol"), "Ally, Person 1 and Person 2");
+    }
+
+    #[test]
+    fn redacts_logs_and_their_names_the_same_way_in_every_log() {
+        let dir = TempDir::new();
+        let (bob, someone) = (dir.join("logs/bob"), dir.join("logs/someone"));
+        write_log(&bob, "Bob", &[
+            message(0, "Someone", "Hi Bob, call me at 555-1234"),
+            message(10, "Carol", "Bye"),
+            message(20, "Bob", "Hey someone, and Other"),
+        ]);
+        write_log(&someone, "Someone", &[message(30, "someone", "Just us, Other")]);
+        let args = RedactArgs {
+            files: vec![bob, someone],
+            output: dir.join("output"),
+            pseudonymize: ["Someone", "Other"].iter().map(|s| s.parse().unwrap()).collect(),
+            remove_sender: vec!["carol".to_owned()],
+            mask: vec![Regex::new(r"\d{3}-\d{4}").unwrap()],
+            mask_with: "[redacted]".to_owned(),
+        };
+        redact(args, &merge_options(&[])).unwrap();
+
+        let output = dir.join("output");
+        assert_eq!(read_log_tab_name(&output.join("bob")).unwrap(), "Bob");
+        assert_eq!(read_log(&output.join("bob")), vec![
+            message(0, "Person 1", "Hi Bob, call me at [redacted]"),
+            message(20, "Bob", "Hey Person 1, and Person 2"),
+        ]);
+        // Private messages are named after the other person, so they are renamed too.
+        assert!(!output.join("someone").exists());
+        assert_eq!(read_log_tab_name(&output.join("person 1")).unwrap(), "Person 1");
+        assert_eq!(read_log(&output.join("person 1")), vec![message(30, "Person 1", "Just us, Person 2")]);
+    }
+
+    #[test]
+    fn removes_messages_and_actions_of_removed_senders() {
+        let redacted = redact_text_export(concat!(
+            "[2021-01-01 12:00] Alice: Hi\n",
+            "[2021-01-01 12:01] Other Person: Hey\n",
+            "[2021-01-01 12:02] *Other Person waves.\n",
+            "[2021-01-01 12:03] Other Personal: Not them\n",
+            "[2021-01-01 12:04] *Alice waves back.\n",
+        ));
+        assert_eq!(redacted, concat!(
+            "[2021-01-01 12:00] Alice: Hi\n",
+            "[2021-01-01 12:03] Other Personal: Not them\n",
+            "[2021-01-01 12:04] *Alice waves back.\n",
+        ));
+    }
+
+    #[test]
+    fn removes_lines_continuing_a_removed_message() {
+        let redacted = redact_text_export(concat!(
+            "[12:00 PM] Other Person: A long post\n",
+            "\n",
+            "[b]that goes on[/b] for a while\n",
+            "[12:01 PM] Alice: Hi\n",
+            "[i]still Alice[/i]\n",
+        ));
+        assert_eq!(redacted, "[12:01 PM] Alice: Hi\n[i]still Alice[/i]\n");
+    }
+}
@@ -0,0 +1,220 @@
+use std::fs::{create_dir, read_dir, remove_dir_all, remove_file, rename, write};
+use std::path::{Path, PathBuf};
+use fchat3_log_lib::fchat_message::FChatMessage;
+use log::{debug, info, warn};
+
+use crate::args::RenameCharacterArgs;
+use crate::{check_input_folder, list_logs, read_log_tab_name, Error, LogWriter, MergeOptions, Reader};
+
+/// Folder in `logs` that rewritten logs are written to before replacing the
+/// originals.
+const REWRITE_FOLDER: &str = ".rename-character";
+/// Written to the rewrite folder once every log was rewritten, so the
+/// rewritten logs can replace the originals.
+const DONE_MARKER: &str = "rewrite.done";
+
+/// Rewrites the sender of a log's messages from the old name to the new one
+/// into the rewrite folder, keeping the rewritten log and its idx only if
+/// any were. Returns how many were.
+fn rewrite_log(log: &Path, rewrite_folder: &Path, old_name: &str, new_name: &str, merge_options: &MergeOptions) -> Result<u64, Error> {
+    let rewritten_log = rewrite_folder.join(log.file_name().unwrap());
+    let mut writer = LogWriter::create(rewritten_log.clone(), read_log_tab_name(log)?)?;
+    let mut rewritten = 0;
+    for message in Reader::open(log, merge_options.mmap)? {
+        let mut message: FChatMessage = message?;
+        // F-Chat names are case-insensitive.
+        if message.sender.eq_ignore_ascii_case(old_name) {
+            message.sender = new_name.to_owned();
+            rewritten += 1;
+        }
+        writer.write(message)?;
+    }
+    writer.finish()?;
+
+    if rewritten == 0 {
+        for path in [rewritten_log.clone(), rewritten_log.with_extension("idx")] {
+            remove_file(&path).map_err(|e| Error::UnableToWrite(path.clone(), e))?;
+        }
+    }
+    Ok(rewritten)
+}
+
+/// Rewrites every log of a character into the rewrite folder, marking it
+/// done once all were. Returns how many messages in how many tabs were.
+fn rewrite_logs(logs_path: &Path, rewrite_folder: &Path, args: &RenameCharacterArgs, merge_options: &MergeOptions) -> Result<(u64, u64), Error> {
+    let mut tabs = 0;
+    let mut total = 0;
+    for log in list_logs(logs_path)? {
+        let rewritten = rewrite_log(&log, rewrite_folder, &args.old_name, &args.new_name, merge_options)?;
+        debug!("Rewrote {} messages in {}.", rewritten, log.to_string_lossy());
+        if rewritten > 0 {
+            tabs += 1;
+            total += rewritten;
+        }
+    }
+    let marker = rewrite_folder.join(DONE_MARKER);
+    write(&marker, "").map_err(|e| Error::UnableToWrite(marker.clone(), e))?;
+    Ok((tabs, total))
+}
+
+/// Replaces the logs with the rewritten ones in a done rewrite folder, then
+/// removes it.
+///
+/// Each idx is moved before its log, so an interruption can at worst leave
+/// a log whose idx was replaced while the rewritten log is still in the
+/// rewrite folder. Running this again finishes the job.
+fn replace_logs(logs_path: &Path, rewrite_folder: &Path) -> Result<(), Error> {
+    let mut rewritten_logs: Vec<PathBuf> = Vec::new();
+    for entry in read_dir(rewrite_folder).map_err(|e| Error::UnableToOpenDirectory(rewrite_folder.to_path_buf(), e))? {
+        let path = entry.map_err(|e| Error::UnableToOpenDirectory(rewrite_folder.to_path_buf(), e))?.path();
+        // Log files do not have a extension.
+        if path.extension().is_none() {
+            rewritten_logs.push(path);
+        }
+    }
+    rewritten_logs.sort();
+    for rewritten_log in rewritten_logs {
+        let log = logs_path.join(rewritten_log.file_name().unwrap());
+        let rewritten_idx = rewritten_log.with_extension("idx");
+        if rewritten_idx.exists() {
+            let idx = log.with_extension("idx");
+            rename(&rewritten_idx, &idx).map_err(|e| Error::UnableToMove(rewritten_idx.clone(), idx.clone(), e))?;
+        }
+        rename(&rewritten_log, &log).map_err(|e| Error::UnableToMove(rewritten_log.clone(), log.clone(), e))?;
+    }
+    remove_dir_all(rewrite_folder).map_err(|e| Error::UnableToRemoveDirectory(rewrite_folder.to_path_buf(), e))
+}
+
+/// Moves a character's folder to their new name, and optionally rewrites
+/// their own messages in every tab to be sent by the new name.
+///
+/// Every log is rewritten before the folder is moved, and the originals are
+/// only replaced after it was. A rename interrupted while replacing them is
+/// finished by running it again.
+pub(crate) fn rename_character(args: RenameCharacterArgs, merge_options: &MergeOptions) -> Result<(), Error> {
+    let old_path = args.folder.join(&args.old_name);
+    let new_path = args.folder.join(&args.new_name);
+
+    let new_rewrite_folder = new_path.join("logs").join(REWRITE_FOLDER);
+    if !old_path.exists() && new_rewrite_folder.join(DONE_MARKER).exists() {
+        info!("Finishing replacing the rewritten logs of an interrupted rename in {}.", new_path.to_string_lossy());
+        return replace_logs(&new_path.join("logs"), &new_rewrite_folder)
+    }
+
+    check_input_folder(&old_path)?;
+    if new_path.exists() {
+        return Err(Error::OutputExists(new_path))
+    }
+
+    let logs_path = old_path.join("logs");
+    let rewrite = args.rewrite_senders && logs_path.is_dir();
+    if args.rewrite_senders && !rewrite {
+        warn!("{} has no logs folder, no messages will be rewritten.", old_path.to_string_lossy());
+    }
+    let mut rewritten = (0, 0);
+    if rewrite {
+        let rewrite_folder = logs_path.join(REWRITE_FOLDER);
+        // Logs are only replaced after moving the folder, so a leftover here has not touched them.
+        if rewrite_folder.exists() {
+            info!("Removing {} left by an interrupted rename.", rewrite_folder.to_string_lossy());
+            remove_dir_all(&rewrite_folder).map_err(|e| Error::UnableToRemoveDirectory(rewrite_folder.clone(), e))?;
+        }
+        create_dir(&rewrite_folder).map_err(|e| Error::UnableToCreateDirectory(rewrite_folder.clone(), e))?;
+        rewritten = match rewrite_logs(&logs_path, &rewrite_folder, &args, merge_options) {
+            Ok(rewritten) => rewritten,
+            Err(e) => {
+                let _ = remove_dir_all(&rewrite_folder);
+                return Err(e)
+            }
+        };
+    }
+
+    if let Err(e) = rename(&old_path, &new_path) {
+        if rewrite {
+            let _ = remove_dir_all(logs_path.join(REWRITE_FOLDER));
+        }
+        return Err(Error::UnableToMove(old_path, new_path, e))
+    }
+    info!("Moved {} to {}.", old_path.to_string_lossy(), new_path.to_string_lossy());
+
+    if rewrite {
+        replace_logs(&new_path.join("logs"), &new_rewrite_folder)?;
+        info!("Rewrote {} messages in {} tabs to be from {}.", rewritten.1, rewritten.0, args.new_name);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{merge_options, message, read_log, write_tab, TempDir};
+
+    fn args(dir: &TempDir) -> RenameCharacterArgs {
+        RenameCharacterArgs {
+            folder: dir.path().to_path_buf(),
+            old_name: "Old Name".to_owned(),
+            new_name: "New Name".to_owned(),
+            rewrite_senders: true,
+        }
+    }
+
+    fn write_logs(dir: &TempDir) {
+        write_tab(dir.path(), "Old Name", "bob", &[message(0, "old name", "Hi"), message(10, "Bob", "Hey")]);
+        write_tab(dir.path(), "Old Name", "carol", &[message(0, "Carol", "Hello")]);
+    }
+
+    fn assert_renamed(dir: &TempDir) {
+        let logs = dir.join("New Name").join("logs");
+        assert_eq!(read_log(&logs.join("bob")), vec![message(0, "New Name", "Hi"), message(10, "Bob", "Hey")]);
+        assert_eq!(read_log_tab_name(&logs.join("bob")).unwrap(), "bob");
+        assert_eq!(read_log(&logs.join("carol")), vec![message(0, "Carol", "Hello")]);
+        assert!(!logs.join(REWRITE_FOLDER).exists());
+        assert!(!dir.join("Old Name").exists());
+    }
+
+    #[test]
+    fn moves_the_folder_and_rewrites_senders() {
+        let dir = TempDir::new();
+        write_logs(&dir);
+        rename_character(args(&dir), &merge_options(&[])).unwrap();
+        assert_renamed(&dir);
+    }
+
+    #[test]
+    fn leaves_everything_alone_when_the_new_name_exists() {
+        let dir = TempDir::new();
+        write_logs(&dir);
+        create_dir(dir.join("New Name")).unwrap();
+        assert!(matches!(rename_character(args(&dir), &merge_options(&[])), Err(Error::OutputExists(_))));
+        assert!(!dir.join("Old Name").join("logs").join(REWRITE_FOLDER).exists());
+        assert_eq!(read_log(&dir.join("Old Name").join("logs").join("bob"))[0].sender, "old name");
+    }
+
+    #[test]
+    fn finishes_an_interrupted_rename() {
+        let dir = TempDir::new();
+        write_logs(&dir);
+        let args = args(&dir);
+        let logs = dir.join("Old Name").join("logs");
+        create_dir(logs.join(REWRITE_FOLDER)).unwrap();
+        rewrite_logs(&logs, &logs.join(REWRITE_FOLDER), &args, &merge_options(&[])).unwrap();
+        rename(dir.join("Old Name"), dir.join("New Name")).unwrap();
+        // Interrupted after replacing the idx but before the log.
+        let logs = dir.join("New Name").join("logs");
+        rename(logs.join(REWRITE_FOLDER).join("bob.idx"), logs.join("bob.idx")).unwrap();
+
+        rename_character(args, &merge_options(&[])).unwrap();
+        assert_renamed(&dir);
+    }
+
+    #[test]
+    fn starts_over_after_an_interrupted_rewrite() {
+        let dir = TempDir::new();
+        write_logs(&dir);
+        let rewrite_folder = dir.join("Old Name").join("logs").join(REWRITE_FOLDER);
+        create_dir(&rewrite_folder).unwrap();
+        write(rewrite_folder.join("bob"), "half written").unwrap();
+        rename_character(args(&dir), &merge_options(&[])).unwrap();
+        assert_renamed(&dir);
+    }
+}
@@ -0,0 +1,220 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Read};
+use std::path::{Path, PathBuf};
+use fchat3_log_lib::FChatWriter;
+use fchat3_log_lib::fchat_index::FChatIndex;
+
+use crate::{Error, Reader};
+
+/// Size of the chunks compared when checking if a log starts another.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// How every source of a tab relates to the one that contains them all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Containment {
+    /// All sources are the same bytes.
+    Identical,
+    /// The other sources' bytes are the same as or the start of this one's.
+    Prefix,
+    /// The other sources' messages are the same as or the start of this
+    /// one's, but not all of their bytes are.
+    Messages,
+}
+
+/// Fills the buffer as much as possible, returning how much was read.
+fn read_chunk(f: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match f.read(&mut buf[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
+
+/// Checks if the short log's bytes are the start of, or all of, the long log.
+fn is_byte_prefix(short: &Path, long: &Path) -> Result<bool, Error> {
+    let mut short_file = File::open(short).map_err(|e| Error::UnableToOpenLog(short.to_path_buf(), e))?;
+    let mut long_file = File::open(long).map_err(|e| Error::UnableToOpenLog(long.to_path_buf(), e))?;
+    let mut short_buf = vec![0; CHUNK_SIZE];
+    let mut long_buf = vec![0; CHUNK_SIZE];
+    loop {
+        let read = read_chunk(&mut short_file, &mut short_buf)
+            .map_err(|e| Error::UnableToOpenLog(short.to_path_buf(), e))?;
+        if read == 0 {
+            return Ok(true)
+        }
+        let long_read = read_chunk(&mut long_file, &mut long_buf[..read])
+            .map_err(|e| Error::UnableToOpenLog(long.to_path_buf(), e))?;
+        if long_read != read || short_buf[..read] != long_buf[..read] {
+            return Ok(false)
+        }
+    }
+}
+
+/// Checks if the short log's messages are the start of, or all of, the long
+/// log's. Catches logs holding the same messages that were written with
+/// different bytes, such as by another version of the client.
+fn is_message_prefix(short: &Path, long: &Path) -> Result<bool, Error> {
+    let mut long_reader = Reader::open(long, false)?;
+    for message in Reader::open(short, false)? {
+        let message = message?;
+        match long_reader.next() {
+            Some(Ok(long_message)) if long_message == message => {},
+            Some(Err(e)) => return Err(e),
+            _ => return Ok(false),
+        }
+    }
+    Ok(true)
+}
+
+/// If every source's bytes or messages are the start of, or all of, the
+/// largest source then there is nothing to merge and the largest can be used
+/// as-is.
+pub(crate) fn find_containing_source(locations: &[PathBuf]) -> Result<Option<(&PathBuf, Containment)>, Error> {
+    let mut sizes = Vec::with_capacity(locations.len());
+    for p in locations {
+        let size = p.metadata().map_err(|e| Error::UnableToOpenLog(p.clone(), e))?.len();
+        sizes.push((size, p));
+    }
+    // Left-most wins when there are several of the same size.
+    let (largest_size, largest) = sizes.iter()
+        .fold(sizes[0], |a, b| if b.0 > a.0 { *b } else { a });
+
+    let mut containment = Containment::Identical;
+    for (size, p) in &sizes {
+        if *p == largest {
+            continue
+        }
+        if is_byte_prefix(p, largest)? {
+            if *size != largest_size && containment == Containment::Identical {
+                containment = Containment::Prefix;
+            }
+        } else if is_message_prefix(p, largest)? {
+            containment = Containment::Messages;
+        } else {
+            return Ok(None)
+        }
+    }
+    Ok(Some((largest, containment)))
+}
+
+/// Copies a source log as-is, along with its idx under the new tab name.
+pub(crate) fn copy_tab(
+    source: &Path,
+    tab_name: String,
+    log_buf: &mut BufWriter<File>,
+    idx_buf: &mut BufWriter<File>,
+) -> Result<(), Error> {
+    let mut log = File::open(source).map_err(|e| Error::UnableToOpenLog(source.to_path_buf(), e))?;
+    io::copy(&mut log, log_buf).map_err(|e| Error::UnableToCopy(source.to_path_buf(), e))?;
+
+    // The offsets in the idx stay valid, only the header needs replacing.
+    let source_idx = source.with_extension("idx");
+    let mut idx = File::open(&source_idx).map_err(|e| Error::UnableToOpenIndex(source_idx.clone(), e))?;
+    FChatIndex::read_header_from_buf(&mut idx)?;
+    FChatWriter::new(idx_buf, tab_name)?;
+    io::copy(&mut idx, idx_buf).map_err(|e| Error::UnableToCopy(source_idx, e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, read, write};
+    use fchat3_log_lib::fchat_message::FChatMessage;
+    use super::*;
+    use crate::read_log_tab_name;
+    use crate::test_util::{message, read_log, write_log, TempDir};
+
+    /// Writes each source's messages as `<dir>/source{i}`.
+    fn write_sources(dir: &TempDir, sources: &[&[FChatMessage]]) -> Vec<PathBuf> {
+        sources.iter().enumerate().map(|(i, messages)| {
+            let path = dir.join(format!("source{}", i));
+            write_log(&path, "bob", messages);
+            path
+        }).collect()
+    }
+
+    fn messages() -> [FChatMessage; 3] {
+        [message(0, "Alice", "Hi"), message(10, "Bob", "Hey"), message(20, "Alice", "Bye")]
+    }
+
+    #[test]
+    fn uses_identical_sources_as_is() {
+        let dir = TempDir::new();
+        let locations = write_sources(&dir, &[&messages(), &messages()]);
+        assert_eq!(find_containing_source(&locations).unwrap(), Some((&locations[0], Containment::Identical)));
+    }
+
+    #[test]
+    fn uses_the_source_the_others_are_byte_prefixes_of() {
+        let dir = TempDir::new();
+        let m = messages();
+        let locations = write_sources(&dir, &[&m[..1], &m, &m[..2]]);
+        assert_eq!(find_containing_source(&locations).unwrap(), Some((&locations[1], Containment::Prefix)));
+    }
+
+    #[test]
+    fn uses_the_complete_source_when_another_was_cut_short() {
+        let dir = TempDir::new();
+        let locations = write_sources(&dir, &[&messages(), &messages()]);
+        // Cut in the middle of the last message, as a crash while writing would.
+        let bytes = read(&locations[0]).unwrap();
+        write(&locations[0], &bytes[..bytes.len() - 3]).unwrap();
+        assert_eq!(find_containing_source(&locations).unwrap(), Some((&locations[1], Containment::Prefix)));
+    }
+
+    /// Changes the size kept after the last message, which is only used to
+    /// read a log backwards, so the log's bytes differ but its messages do not.
+    fn rewrite_last_size(path: &Path) {
+        let mut bytes = read(path).unwrap();
+        let len = bytes.len();
+        bytes[len - 2..].copy_from_slice(&[0, 0]);
+        write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn uses_the_source_the_others_are_message_prefixes_of() {
+        let dir = TempDir::new();
+        let m = messages();
+        let locations = write_sources(&dir, &[&m, &m[..2]]);
+        rewrite_last_size(&locations[1]);
+        assert_eq!(read_log(&locations[1]), m[..2]);
+        assert_eq!(find_containing_source(&locations).unwrap(), Some((&locations[0], Containment::Messages)));
+    }
+
+    #[test]
+    fn does_not_call_sources_with_the_same_messages_but_other_bytes_identical() {
+        let dir = TempDir::new();
+        let locations = write_sources(&dir, &[&messages(), &messages(), &messages()]);
+        rewrite_last_size(&locations[2]);
+        assert_eq!(find_containing_source(&locations).unwrap(), Some((&locations[0], Containment::Messages)));
+    }
+
+    #[test]
+    fn merges_sources_that_are_not_prefixes() {
+        let dir = TempDir::new();
+        let m = messages();
+        let locations = write_sources(&dir, &[&m[..2], &[m[0].clone(), m[2].clone()]]);
+        assert_eq!(find_containing_source(&locations).unwrap(), None);
+    }
+
+    #[test]
+    fn copies_the_log_and_renames_its_idx() {
+        let dir = TempDir::new();
+        let locations = write_sources(&dir, &[&messages()]);
+        let output = dir.join("output");
+        create_dir_all(&output).unwrap();
+        let log_path = output.join("bob");
+        let open = |p: &Path| BufWriter::new(File::create(p).unwrap());
+        let (mut log_buf, mut idx_buf) = (open(&log_path), open(&log_path.with_extension("idx")));
+        copy_tab(&locations[0], "Bob".to_owned(), &mut log_buf, &mut idx_buf).unwrap();
+        drop((log_buf, idx_buf));
+        assert_eq!(read(&log_path).unwrap(), read(&locations[0]).unwrap());
+        assert_eq!(read_log(&log_path), messages());
+        assert_eq!(read_log_tab_name(&log_path).unwrap(), "Bob");
+        let offsets = |p: &Path| FChatIndex::read_from_buf(&mut File::open(p.with_extension("idx")).unwrap()).unwrap().offsets;
+        assert_eq!(offsets(&log_path), offsets(&locations[0]));
+    }
+}
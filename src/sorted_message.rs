@@ -1,22 +1,75 @@
+use std::cmp::Ordering;
+use chrono::NaiveDateTime;
 use fchat3_log_lib::fchat_message::FChatMessage;
 
-#[derive(Debug, PartialEq, Eq)]
-pub(crate) struct SortedMessage(pub(crate) FChatMessage);
+/// A message ordered by when it is placed, then by its source with the
+/// left-most first, then by the order it was read in. Messages sharing a
+/// timestamp so always come out the same way and each source's own order is
+/// kept.
+#[derive(Debug)]
+pub(crate) struct SortedMessage {
+    pub(crate) message: FChatMessage,
+    /// Usually the message's time, see `deduplicate_messages`.
+    pub(crate) placed: NaiveDateTime,
+    /// Index of the source, lower is higher priority.
+    pub(crate) source: usize,
+    /// How many messages were read from the source before this one.
+    pub(crate) seq: u64,
+}
+
+impl SortedMessage {
+    pub(crate) fn new(message: FChatMessage, source: usize, seq: u64) -> Self {
+        Self { placed: message.datetime, message, source, seq }
+    }
+
+    fn key(&self) -> (NaiveDateTime, usize, u64) {
+        (self.placed, self.source, self.seq)
+    }
+}
 
-impl From<FChatMessage> for SortedMessage {
-    fn from(m: FChatMessage) -> Self {
-        Self(m)
+impl PartialEq for SortedMessage {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
     }
 }
 
+impl Eq for SortedMessage {}
+
 impl Ord for SortedMessage {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0.datetime.cmp(&other.0.datetime)
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
     }
 }
 
 impl PartialOrd for SortedMessage {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap;
+    use super::*;
+    use crate::test_util::{at, message};
+
+    #[test]
+    fn orders_by_placed_then_source_then_seq() {
+        let mut later = SortedMessage::new(message(0, "Alice", "Placed later"), 0, 0);
+        later.placed = at(20);
+        let sorted = [
+            SortedMessage::new(message(10, "Bob", "Second source"), 1, 5),
+            SortedMessage::new(message(10, "Alice", "Left-most"), 0, 7),
+            SortedMessage::new(message(10, "Bob", "Read first"), 2, 3),
+            SortedMessage::new(message(10, "Bob", "Read second"), 2, 4),
+            later,
+        ];
+        let mut heap: BinaryHeap<Reverse<SortedMessage>> = sorted.into_iter().rev().map(Reverse).collect();
+        let mut order = Vec::new();
+        while let Some(Reverse(sorted)) = heap.pop() {
+            order.push((sorted.source, sorted.seq));
+        }
+        assert_eq!(order, vec![(0, 7), (1, 5), (2, 3), (2, 4), (0, 0)]);
+    }
+}
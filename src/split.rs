@@ -0,0 +1,150 @@
+use std::fs::create_dir_all;
+use log::info;
+
+use crate::args::SplitArgs;
+use crate::{check_input_file, read_log_tab_name, Error, LogWriter, MergeOptions, Reader};
+
+/// Splits a log into logs named after it with the day, month or year of
+/// their messages, or their number, appended.
+///
+/// A log is started whenever a message's period is later than the current
+/// log's, so out of order messages stay in the log they were found in.
+pub(crate) fn split(args: SplitArgs, merge_options: &MergeOptions) -> Result<(), Error> {
+    let log = args.log;
+    check_input_file(&log)?;
+    let log_name = log.file_name().unwrap().to_string_lossy().into_owned();
+    let tab_name = read_log_tab_name(&log)?;
+    create_dir_all(&args.output)
+        .map_err(|e| Error::UnableToCreateDirectory(args.output.clone(), e))?;
+
+    let reader = Reader::open(&log, merge_options.mmap)?
+        .ordered(merge_options.out_of_order, merge_options.reorder_window);
+    let mut current: Option<(String, LogWriter)> = None;
+    let mut logs = 0;
+    for (index, message) in reader.enumerate() {
+        let message = message?;
+        // The name of the log to start, if this message starts one.
+        let next = match (args.by, args.messages) {
+            (Some(by), _) => {
+                let period = message.datetime.format(by.format()).to_string();
+                match &current {
+                    Some((current_period, _)) if *current_period >= period => None,
+                    _ => Some(period),
+                }
+            },
+            (None, Some(messages)) if index as u64 % messages == 0 => Some(format!("{:04}", logs + 1)),
+            (None, Some(_)) => None,
+            (None, None) => unreachable!("clap requires --by or --messages"),
+        };
+        if let Some(period) = next {
+            if let Some((_, writer)) = current.take() {
+                writer.finish()?;
+            }
+            let path = args.output.join(format!("{}-{}", log_name, period));
+            current = Some((period, LogWriter::create(path, tab_name.clone())?));
+            logs += 1;
+        }
+        current.as_mut().unwrap().1.write(message)?;
+    }
+    if let Some((_, writer)) = current {
+        writer.finish()?;
+    }
+    info!("Split {} into {} logs in {}.", log.to_string_lossy(), logs, args.output.to_string_lossy());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{read_dir, write};
+    use fchat3_log_lib::fchat_message::FChatMessage;
+    use super::*;
+    use crate::args::SplitBy;
+    use crate::test_util::{merge_options, message, read_log, write_log, TempDir};
+
+    /// 2020-09-13, the next day, the next month and the next year.
+    fn messages() -> [FChatMessage; 5] {
+        [
+            message(0, "Alice", "Hi"),
+            message(100, "Bob", "Hey"),
+            message(41_600, "Alice", "Morning"),
+            message(1_510_400, "Bob", "A month later"),
+            message(9_459_200, "Alice", "Happy new year"),
+        ]
+    }
+
+    fn args(dir: &TempDir, by: Option<SplitBy>, messages: Option<u64>) -> SplitArgs {
+        SplitArgs { log: dir.join("bob"), output: dir.join("split"), by, messages }
+    }
+
+    /// Splits the messages, returning the name and messages of each log.
+    fn split_messages(messages: &[FChatMessage], by: Option<SplitBy>, count: Option<u64>) -> Vec<(String, Vec<FChatMessage>)> {
+        let dir = TempDir::new();
+        write_log(&dir.join("bob"), "Bob", messages);
+        split(args(&dir, by, count), &merge_options(&[])).unwrap();
+        let mut logs: Vec<(String, Vec<FChatMessage>)> = Vec::new();
+        for entry in read_dir(dir.join("split")).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none() {
+                assert_eq!(read_log_tab_name(&path).unwrap(), "Bob");
+                logs.push((path.file_name().unwrap().to_string_lossy().into_owned(), read_log(&path)));
+            }
+        }
+        logs.sort_by(|a, b| a.0.cmp(&b.0));
+        logs
+    }
+
+    fn named(name: &str, messages: &[FChatMessage]) -> (String, Vec<FChatMessage>) {
+        (name.to_owned(), messages.to_vec())
+    }
+
+    #[test]
+    fn splits_by_period() {
+        let m = messages();
+        assert_eq!(split_messages(&m, Some(SplitBy::Day), None), vec![
+            named("bob-2020-09-13", &m[..2]),
+            named("bob-2020-09-14", &m[2..3]),
+            named("bob-2020-10-01", &m[3..4]),
+            named("bob-2021-01-01", &m[4..]),
+        ]);
+        assert_eq!(split_messages(&m, Some(SplitBy::Month), None), vec![
+            named("bob-2020-09", &m[..3]),
+            named("bob-2020-10", &m[3..4]),
+            named("bob-2021-01", &m[4..]),
+        ]);
+        assert_eq!(split_messages(&m, Some(SplitBy::Year), None), vec![
+            named("bob-2020", &m[..4]),
+            named("bob-2021", &m[4..]),
+        ]);
+    }
+
+    #[test]
+    fn splits_by_message_count() {
+        let m = messages();
+        assert_eq!(split_messages(&m[..2], None, Some(2)), vec![named("bob-0001", &m[..2])]);
+        assert_eq!(split_messages(&m[..3], None, Some(2)), vec![
+            named("bob-0001", &m[..2]),
+            named("bob-0002", &m[2..3]),
+        ]);
+    }
+
+    #[test]
+    fn keeps_out_of_order_messages_in_the_current_log() {
+        let m = messages();
+        let late = message(200, "Bob", "Sent before midnight");
+        let logs = split_messages(&[m[0].clone(), m[2].clone(), late.clone()], Some(SplitBy::Day), None);
+        assert_eq!(logs, vec![
+            named("bob-2020-09-13", &m[..1]),
+            named("bob-2020-09-14", &[m[2].clone(), late]),
+        ]);
+    }
+
+    #[test]
+    fn refuses_to_overwrite_a_log() {
+        let dir = TempDir::new();
+        write_log(&dir.join("bob"), "Bob", &messages());
+        create_dir_all(dir.join("split")).unwrap();
+        write(dir.join("split").join("bob-2020"), "").unwrap();
+        let result = split(args(&dir, Some(SplitBy::Year), None), &merge_options(&[]));
+        assert!(matches!(result, Err(Error::OutputExists(p)) if p == dir.join("split").join("bob-2020")));
+    }
+}
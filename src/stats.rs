@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use fchat3_log_lib::fchat_message::FChatMessage;
+use humansize::{FormatSize, DECIMAL};
+use rayon::prelude::*;
+
+use crate::args::{StatsArgs, StatsFormat};
+use crate::dedupe::body_parts;
+use crate::{check_input_folder, collect_logs, read_log_tab_name, Aliases, Error, Filters, MergeOptions, Reader};
+
+/// Names of the kinds of message, in the order of `body_parts`.
+const TYPE_NAMES: [&str; 6] = ["message", "action", "ad", "roll", "warn", "event"];
+const WEEKDAY_NAMES: [&str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Counts for a tab or a whole character.
+#[derive(Default)]
+struct Stats {
+    messages: u64,
+    by_type: [u64; 6],
+    senders: HashMap<String, u64>,
+    first: Option<NaiveDateTime>,
+    last: Option<NaiveDateTime>,
+    by_hour: [u64; 24],
+    /// Monday first.
+    by_weekday: [u64; 7],
+    /// Size of the logs and idxs.
+    bytes: u64,
+    /// Characters in every body, for the average length.
+    body_chars: u64,
+}
+
+impl Stats {
+    fn add(&mut self, message: &FChatMessage) {
+        let (kind, body) = body_parts(&message.body);
+        self.messages += 1;
+        self.by_type[kind as usize] += 1;
+        *self.senders.entry(message.sender.clone()).or_default() += 1;
+        self.first = Some(self.first.map_or(message.datetime, |f| f.min(message.datetime)));
+        self.last = Some(self.last.map_or(message.datetime, |l| l.max(message.datetime)));
+        self.by_hour[message.datetime.hour() as usize] += 1;
+        self.by_weekday[message.datetime.weekday().num_days_from_monday() as usize] += 1;
+        self.body_chars += body.chars().count() as u64;
+    }
+
+    fn merge(&mut self, other: &Stats) {
+        self.messages += other.messages;
+        for (total, n) in self.by_type.iter_mut().zip(other.by_type) {
+            *total += n;
+        }
+        for (sender, n) in &other.senders {
+            *self.senders.entry(sender.clone()).or_default() += n;
+        }
+        self.first = self.first.into_iter().chain(other.first).min();
+        self.last = self.last.into_iter().chain(other.last).max();
+        for (total, n) in self.by_hour.iter_mut().zip(other.by_hour) {
+            *total += n;
+        }
+        for (total, n) in self.by_weekday.iter_mut().zip(other.by_weekday) {
+            *total += n;
+        }
+        self.bytes += other.bytes;
+        self.body_chars += other.body_chars;
+    }
+
+    fn average_length(&self) -> f64 {
+        if self.messages == 0 { 0.0 } else { self.body_chars as f64 / self.messages as f64 }
+    }
+
+    /// Most active senders, ties broken by name.
+    fn top_senders(&self, n: usize) -> Vec<(&str, u64)> {
+        let mut senders: Vec<(&str, u64)> = self.senders.iter().map(|(s, n)| (s.as_str(), *n)).collect();
+        senders.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        senders.truncate(n);
+        senders
+    }
+}
+
+struct TabStats {
+    log_name: String,
+    tab_name: String,
+    stats: Stats,
+}
+
+struct CharacterStats {
+    name: String,
+    totals: Stats,
+    tabs: Vec<TabStats>,
+}
+
+/// Reads every source of a tab. With several folders copies of a tab are
+/// all counted.
+fn tab_stats(log_name: &str, locations: &[PathBuf], merge_options: &MergeOptions) -> Result<TabStats, Error> {
+    let mut stats = Stats::default();
+    for location in locations {
+        for p in [location.clone(), location.with_extension("idx")] {
+            stats.bytes += p.metadata().map_err(|e| Error::UnableToOpenLog(p.clone(), e))?.len();
+        }
+        for message in Reader::open(location, merge_options.mmap)? {
+            stats.add(&message?);
+        }
+    }
+    Ok(TabStats {
+        log_name: log_name.to_owned(),
+        tab_name: read_log_tab_name(&locations[0])?,
+        stats,
+    })
+}
+
+fn format_time(time: Option<NaiveDateTime>) -> String {
+    time.map(|t| t.format(TIME_FORMAT).to_string()).unwrap_or_else(|| "-".to_owned())
+}
+
+fn print_table(characters: &[CharacterStats], top: usize) {
+    for character in characters {
+        let totals = &character.totals;
+        println!("=== {} ===", character.name);
+        println!("{} messages in {} tabs, {} on disk, {:.1} characters on average, from {} to {}.",
+            totals.messages,
+            character.tabs.len(),
+            totals.bytes.format_size(DECIMAL),
+            totals.average_length(),
+            format_time(totals.first),
+            format_time(totals.last)
+        );
+        let types: Vec<String> = TYPE_NAMES.iter().zip(totals.by_type)
+            .map(|(name, n)| format!("{} {}", n, name))
+            .collect();
+        println!("Types: {}", types.join(", "));
+        let senders: Vec<String> = totals.top_senders(top).iter()
+            .map(|(name, n)| format!("{} ({})", name, n))
+            .collect();
+        println!("Top senders: {}", senders.join(", "));
+        let hours: Vec<String> = totals.by_hour.iter().map(|n| n.to_string()).collect();
+        println!("By hour: {}", hours.join(" "));
+        let weekdays: Vec<String> = WEEKDAY_NAMES.iter().zip(totals.by_weekday)
+            .map(|(name, n)| format!("{} {}", &name[..3], n))
+            .collect();
+        println!("By weekday: {}", weekdays.join(", "));
+        println!();
+
+        let width = character.tabs.iter().map(|t| t.tab_name.chars().count()).max().unwrap_or(0).max(3);
+        println!("{:<width$}  {:>9}  {:<19}  {:<19}  {:>10}  {:>7}  Top sender",
+            "Tab", "Messages", "First", "Last", "Size", "Avg len", width = width);
+        for tab in &character.tabs {
+            let stats = &tab.stats;
+            let top_sender = stats.top_senders(1).first()
+                .map(|(name, n)| format!("{} ({})", name, n))
+                .unwrap_or_default();
+            println!("{:<width$}  {:>9}  {:<19}  {:<19}  {:>10}  {:>7.1}  {}",
+                tab.tab_name,
+                stats.messages,
+                format_time(stats.first),
+                format_time(stats.last),
+                stats.bytes.format_size(DECIMAL),
+                stats.average_length(),
+                top_sender,
+                width = width
+            );
+        }
+        println!();
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => { let _ = write!(out, "\\u{:04x}", c as u32); },
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_time(time: Option<NaiveDateTime>) -> String {
+    time.map(|t| json_string(&t.format(TIME_FORMAT).to_string())).unwrap_or_else(|| "null".to_owned())
+}
+
+fn json_stats(stats: &Stats, top: usize) -> String {
+    let types: Vec<String> = TYPE_NAMES.iter().zip(stats.by_type)
+        .map(|(name, n)| format!("{}:{}", json_string(name), n))
+        .collect();
+    let senders: Vec<String> = stats.top_senders(top).iter()
+        .map(|(name, n)| format!("{{\"name\":{},\"messages\":{}}}", json_string(name), n))
+        .collect();
+    let hours: Vec<String> = stats.by_hour.iter().map(|n| n.to_string()).collect();
+    let weekdays: Vec<String> = WEEKDAY_NAMES.iter().zip(stats.by_weekday)
+        .map(|(name, n)| format!("{}:{}", json_string(name), n))
+        .collect();
+    format!("\"messages\":{},\"types\":{{{}}},\"top_senders\":[{}],\"first\":{},\"last\":{},\"by_hour\":[{}],\"by_weekday\":{{{}}},\"bytes\":{},\"average_length\":{:.2}",
+        stats.messages,
+        types.join(","),
+        senders.join(","),
+        json_time(stats.first),
+        json_time(stats.last),
+        hours.join(","),
+        weekdays.join(","),
+        stats.bytes,
+        stats.average_length()
+    )
+}
+
+fn print_json(characters: &[CharacterStats], top: usize) {
+    let characters: Vec<String> = characters.iter().map(|character| {
+        let tabs: Vec<String> = character.tabs.iter().map(|tab| {
+            format!("{{\"log\":{},\"tab\":{},{}}}",
+                json_string(&tab.log_name),
+                json_string(&tab.tab_name),
+                json_stats(&tab.stats, top)
+            )
+        }).collect();
+        format!("{{\"name\":{},{},\"tabs\":[{}]}}",
+            json_string(&character.name),
+            json_stats(&character.totals, top),
+            tabs.join(",")
+        )
+    }).collect();
+    println!("{{\"characters\":[{}]}}", characters.join(","));
+}
+
+/// Prints statistics about every character and tab in the folders.
+pub(crate) fn stats(args: StatsArgs, merge_options: &MergeOptions) -> Result<(), Error> {
+    for folder in &args.folders {
+        check_input_folder(folder)?;
+    }
+    let (characters, _, _) = collect_logs(
+        args.folders,
+        &Filters::default(),
+        &Aliases::default(),
+        &Aliases::default()
+    )?;
+
+    let mut character_stats = characters.par_iter().map(|(name, logs)| {
+        let mut tabs = logs.par_iter()
+            .map(|(log_name, locations)| tab_stats(log_name, locations, merge_options))
+            .collect::<Result<Vec<_>, Error>>()?;
+        tabs.sort_by(|a, b| a.log_name.cmp(&b.log_name));
+        let mut totals = Stats::default();
+        for tab in &tabs {
+            totals.merge(&tab.stats);
+        }
+        Ok(CharacterStats { name: name.clone(), totals, tabs })
+    }).collect::<Result<Vec<_>, Error>>()?;
+    character_stats.sort_by(|a, b| a.name.cmp(&b.name));
+
+    match args.format {
+        StatsFormat::Table => print_table(&character_stats, args.top),
+        StatsFormat::Json => print_json(&character_stats, args.top),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use fchat3_log_lib::fchat_message::FChatMessageType;
+    use super::*;
+    use crate::test_util::{at, merge_options, message, write_tab, TempDir};
+
+    #[test]
+    fn counts_messages_by_type_hour_weekday_and_sender() {
+        let dir = TempDir::new();
+        // The first is on a Sunday at 12:26.
+        let mut waves = message(3_600, "Alice", "");
+        waves.body = FChatMessageType::Action(" waves".to_owned());
+        let bob = write_tab(dir.path(), "Alice", "bob", &[
+            message(0, "Bob", "Hi"),
+            waves,
+            message(86_400, "Carol", "Hello"),
+        ]);
+        let carol = write_tab(dir.path(), "Alice", "carol", &[message(90_000, "Carol", "Hey"), message(90_010, "Bob", "Yo")]);
+        let options = merge_options(&[]);
+        let bob = tab_stats("bob", &[bob], &options).unwrap();
+        let carol = tab_stats("carol", &[carol], &options).unwrap();
+        assert_eq!(bob.tab_name, "bob");
+        assert_eq!(bob.stats.messages, 3);
+        assert_eq!(carol.stats.first, Some(at(90_000)));
+
+        let mut totals = Stats::default();
+        totals.merge(&bob.stats);
+        totals.merge(&carol.stats);
+        assert_eq!(totals.messages, 5);
+        assert_eq!(totals.by_type, [4, 1, 0, 0, 0, 0]);
+        assert_eq!((totals.first, totals.last), (Some(at(0)), Some(at(90_010))));
+        let mut by_hour = [0; 24];
+        by_hour[12] = 2;
+        by_hour[13] = 3;
+        assert_eq!(totals.by_hour, by_hour);
+        assert_eq!(totals.by_weekday, [3, 0, 0, 0, 0, 0, 2]);
+        assert_eq!(totals.bytes, bob.stats.bytes + carol.stats.bytes);
+        assert_eq!(totals.average_length(), 18.0 / 5.0);
+        // Bob and Carol are tied, so Bob goes first.
+        assert_eq!(totals.top_senders(2), vec![("Bob", 2), ("Carol", 2)]);
+        assert_eq!(totals.top_senders(5), vec![("Bob", 2), ("Carol", 2), ("Alice", 1)]);
+    }
+
+    #[test]
+    fn escapes_json_strings() {
+        assert_eq!(json_string("Plain name"), "\"Plain name\"");
+        assert_eq!(json_string("\"quoted\" back\\slash"), "\"\\\"quoted\\\" back\\\\slash\"");
+        assert_eq!(json_string("tab\tline\nreturn\r"), "\"tab\\tline\\nreturn\\r\"");
+        assert_eq!(json_string("bell\u{7}escape\u{1b}"), "\"bell\\u0007escape\\u001b\"");
+        assert_eq!(json_string("Ünïcödé ✨"), "\"Ünïcödé ✨\"");
+    }
+}
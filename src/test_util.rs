@@ -0,0 +1,97 @@
+//! Fixtures shared by the tests.
+
+use std::env;
+use std::fs::{create_dir_all, remove_dir_all};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use chrono::NaiveDateTime;
+use clap::Parser;
+use fchat3_log_lib::fchat_message::{FChatMessage, FChatMessageType};
+
+use crate::{merge_tab, Args, LogWriter, MergeOptions, Reader, WrittenTab};
+
+static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+
+/// A folder under the system's temporary folder, removed when dropped.
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    pub(crate) fn new() -> Self {
+        let path = env::temp_dir().join(format!("fchat3-log-merger-test-{}-{}",
+            process::id(),
+            NEXT_DIR.fetch_add(1, Ordering::SeqCst)
+        ));
+        create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub(crate) fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.0);
+    }
+}
+
+/// A time some seconds after an arbitrary point.
+pub(crate) fn at(secs: i64) -> NaiveDateTime {
+    NaiveDateTime::from_timestamp_opt(1_600_000_000 + secs, 0).unwrap()
+}
+
+pub(crate) fn message(secs: i64, sender: &str, body: &str) -> FChatMessage {
+    FChatMessage { datetime: at(secs), sender: sender.to_owned(), body: FChatMessageType::Message(body.to_owned()) }
+}
+
+/// Writes a log and its idx, creating the folders it is in.
+pub(crate) fn write_log(path: &Path, tab_name: &str, messages: &[FChatMessage]) {
+    create_dir_all(path.parent().unwrap()).unwrap();
+    let mut writer = LogWriter::create(path.to_path_buf(), tab_name.to_owned()).unwrap();
+    for message in messages {
+        writer.write(message.clone()).unwrap();
+    }
+    writer.finish().unwrap();
+}
+
+/// Writes a log into a data folder as `<data>/<character>/logs/<log_name>`.
+pub(crate) fn write_tab(data: &Path, character: &str, log_name: &str, messages: &[FChatMessage]) -> PathBuf {
+    let path = data.join(character).join("logs").join(log_name);
+    write_log(&path, log_name, messages);
+    path
+}
+
+pub(crate) fn read_log(path: &Path) -> Vec<FChatMessage> {
+    Reader::open(path, false).unwrap().map(Result::unwrap).collect()
+}
+
+/// Merge options as parsed from the command line with the extra args.
+pub(crate) fn merge_options(extra: &[&str]) -> MergeOptions {
+    let args = Args::parse_from(["fchat3-log-merger", "-f", "a", "b", "-o", "out"].iter().chain(extra));
+    MergeOptions::new(&args)
+}
+
+/// Writes the sources of a tab into the folder and merges them, returning
+/// the sources and what was written.
+pub(crate) fn merge_into(dir: &TempDir, sources: &[&[FChatMessage]], merge_options: &MergeOptions) -> (Vec<PathBuf>, WrittenTab) {
+    let locations: Vec<PathBuf> = sources.iter().enumerate()
+        .map(|(i, messages)| write_tab(&dir.join(format!("source{}", i)), "alice", "bob", messages))
+        .collect();
+    let output = dir.join("output");
+    create_dir_all(&output).unwrap();
+    let written = merge_tab("alice", "bob", &locations, &output, merge_options).unwrap();
+    (locations, written)
+}
+
+/// Merges a tab with the sources' messages, returning the merged messages.
+pub(crate) fn merge_sources(sources: &[&[FChatMessage]], merge_options: &MergeOptions) -> Vec<FChatMessage> {
+    let dir = TempDir::new();
+    let (_, written) = merge_into(&dir, sources, merge_options);
+    read_log(&written.log_path)
+}
@@ -0,0 +1,353 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::{BufReader, Seek};
+use std::path::{Path, PathBuf};
+use chrono::{Duration, NaiveDateTime};
+use fchat3_log_lib::fchat_index::FChatIndex;
+use fchat3_log_lib::fchat_message::FChatMessage;
+use log::debug;
+
+use crate::dedupe::format_message;
+use crate::{Conflict, Error, Keep, MergeOptions, Reader, Resolver};
+
+/// What was written for a merged tab.
+pub(crate) struct WrittenTab {
+    pub(crate) log_path: PathBuf,
+    pub(crate) tab_name: String,
+    /// How many messages were written, unknown when a source was copied as-is.
+    pub(crate) messages: Option<u64>,
+    /// Source copied as-is, whose messages are counted instead when verifying.
+    pub(crate) copied_from: Option<PathBuf>,
+}
+
+fn failed(log_path: &Path, reason: String) -> Error {
+    Error::VerificationFailed(log_path.to_path_buf(), reason)
+}
+
+/// Re-reads a merged tab to check its idx names the tab and points at the
+/// start of messages, its messages parse and are in order, it has as many
+/// messages as were written, and every message of its sources made it in or,
+/// when only messages in every source are kept, every merged message is in
+/// each source. A message counts as in a log when a copy of it within the
+/// time difference is, as the resolver compares them.
+///
+/// The merge keeps each source's order, so the merged log is only expected
+/// to be in order when every source is, as read for the merge. When one is
+/// not, the order is not checked and messages are looked up without relying
+/// on it, holding the log they are looked up in in memory.
+pub(crate) fn verify_tab(
+    written: &WrittenTab,
+    locations: &[PathBuf],
+    conflict: Conflict,
+    merge_options: &MergeOptions
+) -> Result<(), Error> {
+    let log_path = &written.log_path;
+    let idx_path = log_path.with_extension("idx");
+    let f = File::open(&idx_path).map_err(|e| Error::UnableToOpenIndex(idx_path.clone(), e))?;
+    let index = FChatIndex::read_from_buf(&mut BufReader::new(f))?;
+    if index.name != written.tab_name {
+        return Err(failed(log_path, format!("idx names the tab `{}` instead of `{}`", index.name, written.tab_name)))
+    }
+
+    let mut sorted = true;
+    for location in locations {
+        sorted &= is_sorted(source_reader(location, merge_options)?)?;
+    }
+
+    let mut count = 0;
+    let mut last: Option<NaiveDateTime> = None;
+    // Where each message starts in the log.
+    let mut starts = HashSet::new();
+    let mut reader = Reader::open(log_path, merge_options.mmap)?;
+    loop {
+        let start = reader.buf.stream_position().map_err(|e| Error::UnableToOpenLog(log_path.clone(), e))?;
+        let message = match reader.next() {
+            Some(message) => message?,
+            None => break,
+        };
+        starts.insert(start);
+        if sorted && matches!(last, Some(last) if message.datetime < last) {
+            return Err(failed(log_path, format!("message {} is out of order: {}", count + 1, format_message(&message))))
+        }
+        last = Some(message.datetime);
+        count += 1;
+    }
+    for (day, offset) in &index.offsets {
+        if !starts.contains(offset) {
+            return Err(failed(log_path, format!("idx entry for day {} points at {}, which is not the start of a message", day, offset)))
+        }
+    }
+
+    let expected = match (written.messages, &written.copied_from) {
+        (Some(messages), _) => Some(messages),
+        (None, Some(source)) => Some(Reader::open(source, merge_options.mmap)?.try_fold(0, |n, m| m.map(|_| n + 1))?),
+        (None, None) => None,
+    };
+    if let Some(expected) = expected {
+        if count != expected {
+            return Err(failed(log_path, format!("{} messages were expected but {} were read back", expected, count)))
+        }
+    }
+
+    let resolver = Resolver::new(conflict, locations)?;
+    for (index, location) in locations.iter().enumerate() {
+        let left_most = index == 0;
+        let source = source_reader(location, merge_options)?
+            .filter(|m| !matches!(m, Ok(m) if skipped(m, left_most, merge_options)));
+        let merged = Reader::open(log_path, merge_options.mmap)?;
+        if conflict.keep == Keep::Union {
+            if let Some(message) = find_missing(source, merged, sorted, &resolver, merge_options.time_diff)? {
+                return Err(failed(log_path, format!("{} has a message that is missing: {}",
+                    location.to_string_lossy(),
+                    format_message(&message)
+                )))
+            }
+        } else {
+            let merged = merged.filter(|m| !matches!(m, Ok(m) if skipped(m, left_most, merge_options)));
+            if let Some(message) = find_missing(merged, source, sorted, &resolver, merge_options.time_diff)? {
+                return Err(failed(log_path, format!("has a message missing from {}: {}",
+                    location.to_string_lossy(),
+                    format_message(&message)
+                )))
+            }
+        }
+    }
+    debug!("Verified {} with {} messages.", log_path.to_string_lossy(), count);
+    Ok(())
+}
+
+/// Reads a source as it was read for the merge.
+fn source_reader<'a>(source: &Path, merge_options: &MergeOptions) -> Result<Reader<'a>, Error> {
+    Ok(Reader::open(source, merge_options.mmap)?
+        .bounded(merge_options.since, merge_options.until)
+        .ordered(merge_options.out_of_order, merge_options.reorder_window)
+        .quiet())
+}
+
+fn is_sorted(reader: Reader) -> Result<bool, Error> {
+    let mut last = None;
+    for message in reader {
+        let message = message?;
+        if matches!(last, Some(last) if message.datetime < last) {
+            return Ok(false)
+        }
+        last = Some(message.datetime);
+    }
+    Ok(true)
+}
+
+/// If the merge skipped a source's message on purpose, such as when fast
+/// forwarding. Left-most messages written before fast forwarding are not in
+/// the other sources either.
+fn skipped(message: &FChatMessage, left_most: bool, merge_options: &MergeOptions) -> bool {
+    !left_most && matches!(merge_options.fast_forward, Some(to) if message.datetime <= to)
+}
+
+/// Messages of a log looked up by the resolver's key, oldest first.
+struct Copies<'a> {
+    resolver: &'a Resolver,
+    by_key: HashMap<u64, VecDeque<FChatMessage>>,
+}
+
+impl<'a> Copies<'a> {
+    fn new(resolver: &'a Resolver) -> Self {
+        Self { resolver, by_key: HashMap::new() }
+    }
+
+    fn push(&mut self, message: FChatMessage) -> u64 {
+        let key = self.resolver.key(&message);
+        self.by_key.entry(key).or_default().push_back(message);
+        key
+    }
+
+    fn pop(&mut self, key: u64) {
+        if let Some(messages) = self.by_key.get_mut(&key) {
+            messages.pop_front();
+            if messages.is_empty() {
+                self.by_key.remove(&key);
+            }
+        }
+    }
+
+    fn has_copy(&self, message: &FChatMessage, time_diff: Duration) -> bool {
+        self.by_key.get(&self.resolver.key(message)).into_iter().flatten().any(|copy| {
+            copy.datetime <= message.datetime + time_diff &&
+                message.datetime <= copy.datetime + time_diff &&
+                self.resolver.is_duplicate(copy, message)
+        })
+    }
+}
+
+/// Finds a message of `checked` that `container` has no copy of within the
+/// time difference. When both are in order they are read side by side,
+/// otherwise `container` is read into memory first.
+fn find_missing(
+    checked: impl Iterator<Item = Result<FChatMessage, Error>>,
+    container: impl Iterator<Item = Result<FChatMessage, Error>>,
+    sorted: bool,
+    resolver: &Resolver,
+    time_diff: Duration
+) -> Result<Option<FChatMessage>, Error> {
+    let mut copies = Copies::new(resolver);
+    if !sorted {
+        for message in container {
+            copies.push(message?);
+        }
+        for message in checked {
+            let message = message?;
+            if !copies.has_copy(&message, time_diff) {
+                return Ok(Some(message))
+            }
+        }
+        return Ok(None)
+    }
+
+    let mut container = container.peekable();
+    // Times and keys of the copies held, oldest first.
+    let mut held = VecDeque::new();
+    for message in checked {
+        let message = message?;
+        while let Some(next) = container.next_if(|m| !matches!(m, Ok(m) if m.datetime > message.datetime + time_diff)) {
+            let next = next?;
+            let datetime = next.datetime;
+            held.push_back((datetime, copies.push(next)));
+        }
+        while let Some((datetime, key)) = held.front().copied() {
+            if datetime + time_diff >= message.datetime {
+                break
+            }
+            held.pop_front();
+            copies.pop(key);
+        }
+        if !copies.has_copy(&message, time_diff) {
+            return Ok(Some(message))
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{copy, read, remove_file, OpenOptions};
+    use std::io::Write;
+    use super::*;
+    use crate::test_util::{merge_into, merge_options, merge_sources, message, write_log, TempDir};
+
+    fn verify(written: &WrittenTab, locations: &[PathBuf], merge_options: &MergeOptions) -> Result<(), Error> {
+        verify_tab(written, locations, merge_options.conflict, merge_options)
+    }
+
+    #[test]
+    fn accepts_a_merged_tab() {
+        let dir = TempDir::new();
+        let a = [message(0, "Alice", "Hi"), message(100_000, "Bob", "Hey")];
+        let b = [message(0, "Alice", "Hi"), message(200_000, "Alice", "Bye")];
+        let options = merge_options(&[]);
+        let (locations, written) = merge_into(&dir, &[&a, &b], &options);
+        verify(&written, &locations, &options).unwrap();
+    }
+
+    #[test]
+    fn counts_the_source_of_a_copied_tab() {
+        let dir = TempDir::new();
+        let a = [message(0, "Alice", "Hi"), message(10, "Bob", "Hey")];
+        let options = merge_options(&[]);
+        let (locations, written) = merge_into(&dir, &[&a, &a[..1]], &options);
+        assert_eq!(written.messages, None);
+        verify(&written, &locations, &options).unwrap();
+
+        // A message the source does not have is caught by the count.
+        let mut log = OpenOptions::new().append(true).open(&written.log_path).unwrap();
+        let extra = dir.join("extra");
+        write_log(&extra, "bob", &[message(20, "Bob", "Bye")]);
+        log.write_all(&read(&extra).unwrap()).unwrap();
+        assert!(matches!(verify(&written, &locations, &options), Err(Error::VerificationFailed(..))));
+    }
+
+    #[test]
+    fn rejects_idx_offsets_inside_a_message() {
+        let dir = TempDir::new();
+        let a = [message(0, "Alice", "Hi"), message(100_000, "Bob", "Hey")];
+        let options = merge_options(&[]);
+        let (locations, written) = merge_into(&dir, &[&a], &options);
+        verify(&written, &locations, &options).unwrap();
+
+        // An idx for a log whose first message is longer points into the middle of the second.
+        let longer = dir.join("longer");
+        write_log(&longer, "bob", &[message(0, "Alice", "Hi there"), a[1].clone()]);
+        copy(longer.with_extension("idx"), written.log_path.with_extension("idx")).unwrap();
+        assert!(matches!(verify(&written, &locations, &options), Err(Error::VerificationFailed(..))));
+    }
+
+    /// A source whose clock jumped back further than the time difference.
+    fn jumped_back() -> ([FChatMessage; 4], [FChatMessage; 2]) {
+        (
+            [message(0, "Alice", "Hi"), message(100, "Bob", "Hey"), message(50, "Alice", "Oops"), message(200, "Bob", "Bye")],
+            [message(1, "Alice", "Hi"), message(150, "Carol", "Hello")],
+        )
+    }
+
+    #[test]
+    fn accepts_sources_that_jump_back() {
+        let (a, b) = jumped_back();
+        for extra in [
+            &["--out-of-order", "warn"][..],
+            &["--out-of-order", "preserve"],
+            &["--out-of-order", "sort", "--reorder-window", "2"],
+            &["--out-of-order", "sort", "--reorder-window", "1"],
+            &["--out-of-order", "sort", "--reorder-window", "2", "--always-parse"],
+        ] {
+            let dir = TempDir::new();
+            let options = merge_options(&[&["-d", "5s"], extra].concat());
+            let (locations, written) = merge_into(&dir, &[&a, &b], &options);
+            verify(&written, &locations, &options).unwrap_or_else(|e| panic!("{:?}: {}", extra, e));
+        }
+    }
+
+    #[test]
+    fn merges_sources_that_jump_back_as_asked() {
+        let (a, b) = jumped_back();
+        let kept = vec![a[0].clone(), a[1].clone(), a[2].clone(), b[1].clone(), a[3].clone()];
+        let sorted = vec![a[0].clone(), a[2].clone(), a[1].clone(), b[1].clone(), a[3].clone()];
+        for (extra, expected) in [
+            (&["--out-of-order", "warn"][..], &kept),
+            (&["--out-of-order", "preserve"], &kept),
+            (&["--out-of-order", "sort", "--reorder-window", "2"], &sorted),
+            // The jump is further back than the window.
+            (&["--out-of-order", "sort", "--reorder-window", "1"], &kept),
+        ] {
+            let options = merge_options(&[&["-d", "5s"], extra].concat());
+            assert_eq!(&merge_sources(&[&a, &b], &options), expected, "{:?}", extra);
+        }
+    }
+
+    #[test]
+    fn rejects_missing_messages_from_sources_that_jump_back() {
+        let (a, b) = jumped_back();
+        let dir = TempDir::new();
+        let options = merge_options(&["-d", "5s"]);
+        let (locations, written) = merge_into(&dir, &[&a, &b], &options);
+        // The merged log without the message from before the jump.
+        remove_file(&written.log_path).unwrap();
+        remove_file(written.log_path.with_extension("idx")).unwrap();
+        write_log(&written.log_path, "bob", &[a[0].clone(), a[1].clone(), b[1].clone(), a[3].clone()]);
+        let written = WrittenTab { messages: Some(4), ..written };
+        assert!(matches!(verify(&written, &locations, &options), Err(Error::VerificationFailed(..))));
+    }
+
+    #[test]
+    fn rejects_messages_missing_from_a_source_when_intersecting() {
+        let dir = TempDir::new();
+        let a = [message(0, "Alice", "Hi"), message(20, "Carol", "Only in a")];
+        let b = [message(1, "Alice", "Hi!")];
+        let options = merge_options(&["-d", "5s", "--prefer", "longest-body", "--keep", "intersection"]);
+        let (locations, written) = merge_into(&dir, &[&a, &b], &options);
+        verify(&written, &locations, &options).unwrap();
+
+        remove_file(&written.log_path).unwrap();
+        remove_file(written.log_path.with_extension("idx")).unwrap();
+        write_log(&written.log_path, "bob", &[message(0, "Alice", "Hi!"), a[1].clone()]);
+        let written = WrittenTab { messages: Some(2), ..written };
+        assert!(matches!(verify(&written, &locations, &options), Err(Error::VerificationFailed(..))));
+    }
+}